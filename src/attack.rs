@@ -0,0 +1,357 @@
+use crate::config::MachineConfig;
+use crate::enigma::Enigma;
+use crate::error::EnigmaError;
+use crate::rotor::{try_alpha_index, ALPHA, BETA, GAMMA};
+use crate::scoring::{IndexOfCoincidence, Scorer};
+use crate::text;
+
+// Search space and effort of a ciphertext-only attack. The defaults cover
+// an M3 with wheels I-V and reflector B, keeping the ten best settings
+// found by the wheel order search and allowing up to ten plugboard cables.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttackSpec {
+    pub model: String,
+    pub rotor_pool: Vec<i32>,
+    pub reflectors: Vec<char>,
+    pub candidates: usize,
+    pub max_plugs: usize
+}
+
+impl Default for AttackSpec {
+    fn default() -> Self {
+        Self {
+            model: "M3".to_string(),
+            rotor_pool: vec![1, 2, 3, 4, 5],
+            reflectors: vec!['B'],
+            candidates: 10,
+            max_plugs: 10
+        }
+    }
+}
+
+impl AttackSpec {
+    // Every ordered choice of three wheels from the pool, each preceded by
+    // one of the Greek wheels for the M4
+    pub fn wheel_orders(&self) -> Vec<Vec<i32>> {
+        let mut orders = Vec::new();
+
+        for &left in &self.rotor_pool {
+            for &middle in self.rotor_pool.iter().filter(|&&x| x != left) {
+                for &right in self.rotor_pool.iter().filter(|&&x| x != left && x != middle) {
+                    orders.push(vec![left, middle, right]);
+                }
+            }
+        }
+
+        if self.model == "M4" {
+            orders = [BETA, GAMMA].iter()
+                .flat_map(|&greek| orders.iter().map(move |x| [vec![greek], x.clone()].concat()))
+                .collect();
+        }
+
+        orders
+    }
+}
+
+// Machine settings proposed by an attack, with the score of the text they give
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    pub config: MachineConfig,
+    pub score: f64
+}
+
+fn key_for(index: usize, n_rotors: usize) -> String {
+    (0..n_rotors).rev().map(|i| ALPHA[(index / 26usize.pow(i as u32)) % 26]).collect()
+}
+
+// Deciphers the letters from the given start position into the buffer,
+// returning the score of the result
+fn score_key(machine: &mut Enigma, key: &str, letters: &[u8], scorer: &dyn Scorer, buffer: &mut Vec<u8>) -> Result<f64, EnigmaError> {
+    machine.try_set_key(key.to_string())?;
+
+    buffer.clear();
+    buffer.extend(letters.iter().map(|&x| machine.type_index(x)));
+
+    Ok(scorer.score(buffer))
+}
+
+// Keeps the best candidates found so far, in order of descending score
+fn keep_best(best: &mut Vec<Candidate>, candidate: Candidate, n_best: usize) {
+    if best.len() == n_best && best.last().map(|x| x.score >= candidate.score).unwrap_or(false) {
+        return;
+    }
+
+    let position = best.iter().position(|x| x.score < candidate.score).unwrap_or(best.len());
+    best.insert(position, candidate);
+    best.truncate(n_best);
+}
+
+// Tries each wheel order, reflector and start position with the rings at
+// A and no plugboard, the correct rotor setting standing out through the
+// raised index of coincidence of its partly correct decipherment
+fn search_rotors(letters: &[u8], spec: &AttackSpec) -> Result<Vec<Candidate>, EnigmaError> {
+    let mut best = Vec::with_capacity(spec.candidates + 1);
+    let mut buffer = Vec::with_capacity(letters.len());
+
+    for order in spec.wheel_orders() {
+        for &reflector in &spec.reflectors {
+            let mut machine = Enigma::try_new(order.clone(), reflector, spec.model.clone())?;
+            let n_rotors = order.len();
+
+            for index in 0..26usize.pow(n_rotors as u32) {
+                let key = key_for(index, n_rotors);
+                let score = score_key(&mut machine, &key, letters, &IndexOfCoincidence, &mut buffer)?;
+
+                if best.len() < spec.candidates || best.last().map(|x: &Candidate| x.score < score).unwrap_or(true) {
+                    let mut config = machine.to_config();
+                    config.start = key;
+                    keep_best(&mut best, Candidate { config, score }, spec.candidates);
+                }
+            }
+        }
+    }
+
+    Ok(best)
+}
+
+// Turning the ring and the start position of a rotor together leaves its
+// wiring where it was and moves only its turnover, so the ring settings of
+// the right and then the middle rotor are found one at a time
+fn refine_rings(letters: &[u8], candidate: &Candidate, scorer: &dyn Scorer) -> Result<Candidate, EnigmaError> {
+    let mut machine = Enigma::try_from_config(&candidate.config)?;
+    let n_rotors = candidate.config.rotors.len();
+    let mut buffer = Vec::with_capacity(letters.len());
+
+    let mut rings = vec![0u8; n_rotors];
+    let mut start: Vec<u8> = machine.get_key().chars().map(|x| try_alpha_index(x).map(|i| i as u8)).collect::<Result<Vec<u8>, EnigmaError>>()?;
+    let mut best_score = score_key(&mut machine, &candidate.config.start, letters, scorer, &mut buffer)?;
+
+    for slot in [n_rotors - 1, n_rotors - 2] {
+        let (mut best_ring, mut best_start) = (rings[slot], start[slot]);
+
+        for shift in 1..26 {
+            rings[slot] = shift;
+            let mut trial = start.clone();
+            trial[slot] = (start[slot] + shift) % 26;
+
+            machine.ringstellung(rings.iter().map(|&x| x as i32).collect());
+            let key: String = trial.iter().map(|&x| ALPHA[x as usize]).collect();
+            let score = score_key(&mut machine, &key, letters, scorer, &mut buffer)?;
+
+            if score > best_score {
+                best_score = score;
+                best_ring = shift;
+                best_start = trial[slot];
+            }
+        }
+
+        rings[slot] = best_ring;
+        start[slot] = best_start;
+    }
+
+    let mut config = candidate.config.clone();
+    config.rings = rings.iter().map(|&x| ALPHA[x as usize]).collect();
+    config.start = start.iter().map(|&x| ALPHA[x as usize]).collect();
+
+    Ok(Candidate { config, score: best_score })
+}
+
+// Plugboards one change away from the given one: a cable removed, one end
+// of a cable moved to a free letter, the ends of two cables exchanged, or
+// a new cable between free letters
+fn plugboard_moves(pairs: &[(char, char)], max_plugs: usize) -> Vec<Vec<(char, char)>> {
+    let plugged: Vec<char> = pairs.iter().flat_map(|&(a, b)| [a, b]).collect();
+    let free: Vec<char> = ALPHA.iter().filter(|x| !plugged.contains(x)).cloned().collect();
+    let mut moves = Vec::new();
+
+    for i in 0..pairs.len() {
+        let mut removed = pairs.to_vec();
+        removed.remove(i);
+        moves.push(removed);
+    }
+
+    for (i, &(a, b)) in pairs.iter().enumerate() {
+        for &c in &free {
+            for kept in [a, b] {
+                let mut moved = pairs.to_vec();
+                moved[i] = (kept, c);
+                moves.push(moved);
+            }
+        }
+    }
+
+    for i in 0..pairs.len() {
+        for j in i + 1..pairs.len() {
+            let ((a, b), (c, d)) = (pairs[i], pairs[j]);
+            for exchanged in [[(a, c), (b, d)], [(a, d), (b, c)]] {
+                let mut moved = pairs.to_vec();
+                moved[i] = exchanged[0];
+                moved[j] = exchanged[1];
+                moves.push(moved);
+            }
+        }
+    }
+
+    if pairs.len() < max_plugs {
+        for (i, &a) in free.iter().enumerate() {
+            for &b in &free[i + 1..] {
+                let mut added = pairs.to_vec();
+                added.push((a, b));
+                moves.push(added);
+            }
+        }
+    }
+
+    moves
+}
+
+fn pairs_text(pairs: &[(char, char)]) -> String {
+    pairs.iter().map(|(a, b)| format!("{}{}", a, b)).collect::<Vec<String>>().join(" ")
+}
+
+// Steepest ascent over the plugboard, each round making the single change
+// which raises the score the most until no change helps
+fn climb_plugboard(letters: &[u8], candidate: &Candidate, max_plugs: usize, scorer: &dyn Scorer) -> Result<Candidate, EnigmaError> {
+    let mut machine = Enigma::try_from_config(&candidate.config)?;
+    let mut buffer = Vec::with_capacity(letters.len());
+    let start = candidate.config.start.clone();
+    let max_plugs = max_plugs.min(crate::plugboard::MAX_PAIRS);
+
+    let mut pairs = machine.plugboard_pairs();
+    let mut best_score = score_key(&mut machine, &start, letters, scorer, &mut buffer)?;
+
+    loop {
+        let mut best_move = None;
+
+        for trial in plugboard_moves(&pairs, max_plugs) {
+            machine.try_set_plugboard(&pairs_text(&trial))?;
+            let score = score_key(&mut machine, &start, letters, scorer, &mut buffer)?;
+
+            if score > best_score {
+                best_score = score;
+                best_move = Some(trial);
+            }
+        }
+
+        match best_move {
+            Some(trial) => pairs = trial,
+            None => break
+        }
+    }
+
+    machine.try_set_plugboard(&pairs_text(&pairs))?;
+    machine.try_set_key(start)?;
+    Ok(Candidate { config: machine.to_config(), score: best_score })
+}
+
+// Rings and plugboard for a rotor setting from the search. Cables are first
+// found on index of coincidence, as a language model finds few correct ones
+// from an empty board, which then lets the rings be told apart before the
+// plugboard is finished on the scorer.
+fn refine(letters: &[u8], candidate: &Candidate, max_plugs: usize, scorer: &dyn Scorer) -> Result<Candidate, EnigmaError> {
+    let candidate = climb_plugboard(letters, candidate, max_plugs, &IndexOfCoincidence)?;
+    let candidate = refine_rings(letters, &candidate, &IndexOfCoincidence)?;
+    climb_plugboard(letters, &candidate, max_plugs, scorer)
+}
+
+// Ciphertext-only attack after Gillogly: the wheel order and start position
+// are found by index of coincidence, then the rings and plugboard refined
+// by hill climbing. Returns the candidates ranked by the given scorer.
+pub fn try_attack_with(ciphertext: &str, spec: &AttackSpec, scorer: &dyn Scorer) -> Result<Vec<Candidate>, EnigmaError> {
+    let letters = text::strip_groups(ciphertext).chars()
+        .map(|x| try_alpha_index(x.to_ascii_uppercase()).map(|i| i as u8))
+        .collect::<Result<Vec<u8>, EnigmaError>>()?;
+
+    let mut ranked = Vec::new();
+
+    for candidate in search_rotors(&letters, spec)? {
+        ranked.push(refine(&letters, &candidate, spec.max_plugs, scorer)?);
+    }
+
+    ranked.sort_by(|a, b| b.score.total_cmp(&a.score));
+    Ok(ranked)
+}
+
+// The attack scored throughout by index of coincidence
+pub fn try_attack(ciphertext: &str, spec: &AttackSpec) -> Result<Vec<Candidate>, EnigmaError> {
+    try_attack_with(ciphertext, spec, &IndexOfCoincidence)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AttackSpec, Candidate};
+    use crate::config::MachineConfig;
+    use crate::enigma::Enigma;
+    use crate::scoring::{Language, NgramScorer, Scorer};
+
+    const PLAINTEXT: &str = "DASOBERKOMMANDODERWEHRMACHTGIBTBEKANNTAACHENISTRETTETDURCHGEBUENDELTENEINSATZDERHILFSKRAEFTEKONNTEDIEBEDROHUNGABGEWENDETUNDDIERETTUNGDERSTADTGEGENXEINSXAQTXDREIXNULLXUHRSICHERGESTELLTWERDENXDIEBEVOELKERUNGWIRDGEBETENDIEANWEISUNGENDERBEHOERDENZUBEFOLGENUNDRUHEZUBEWAHREN";
+
+    #[test]
+    fn test_wheel_orders() {
+        assert!(super::key_for(26 * 26 + 2, 3) == "BAC");
+        assert!(AttackSpec::default().wheel_orders().len() == 60);
+        assert!(AttackSpec { rotor_pool: (1..=8).collect(), ..AttackSpec::default() }.wheel_orders().len() == 336);
+
+        let m4 = AttackSpec { model: "M4".to_string(), rotor_pool: vec![1, 2, 3], ..AttackSpec::default() };
+        assert!(m4.wheel_orders().len() == 12 && m4.wheel_orders().iter().all(|x| x.len() == 4));
+    }
+
+    #[test]
+    fn test_keep_best() {
+        let candidate = |score| Candidate { config: MachineConfig::default(), score };
+        let mut best = Vec::new();
+
+        for score in [0.3, 0.1, 0.5, 0.2, 0.4] {
+            super::keep_best(&mut best, candidate(score), 3);
+        }
+        assert!(best.iter().map(|x| x.score).collect::<Vec<f64>>() == vec![0.5, 0.4, 0.3]);
+    }
+
+    #[test]
+    fn test_attack() {
+        let mut machine = Enigma::new(vec![2, 4, 5], 'B', "M3".to_string());
+        machine.set_ring_settings("AAC");
+        machine.set_plugboard("AV BS CG DL FU");
+        machine.set_key("QMT".to_string());
+        let cipher = machine.type_phrase(PLAINTEXT.to_string());
+
+        let spec = AttackSpec { rotor_pool: vec![2, 4, 5], candidates: 3, max_plugs: 5, ..AttackSpec::default() };
+        let ranked = super::try_attack(&cipher, &spec).unwrap();
+        assert!(ranked.len() == 3);
+        assert!(ranked.windows(2).all(|x| x[0].score >= x[1].score));
+
+        // The ring of the left rotor is never recovered, so it is left at A here
+        machine.set_key("QMT".to_string());
+        assert!(ranked[0].config == machine.to_config());
+        assert!(ranked[0].score > 0.07 && ranked[1].score < 0.06);
+
+        let mut solved = Enigma::from_config(&ranked[0].config);
+        assert!(solved.decode_phrase(&cipher).starts_with(PLAINTEXT));
+
+        assert!(super::try_attack("EDPUD 1RGYS", &spec).is_err());
+    }
+
+    #[test]
+    fn test_plugboard_quadgrams() {
+        let mut machine = Enigma::new(vec![2, 4, 5], 'B', "M3".to_string());
+        machine.set_ring_settings("AAC");
+        machine.set_plugboard("AV BS CG DL FU HZ IN KM OW RX");
+        machine.set_key("QMT".to_string());
+        let cipher = machine.type_phrase(PLAINTEXT.to_string());
+        let letters: Vec<u8> = crate::text::strip_groups(&cipher).chars().map(|x| crate::rotor::alpha_index(x) as u8).collect();
+
+        // As found by the rotor search, with the rings at A and the start
+        // position of the right rotor turned back to match
+        let mut config = machine.to_config();
+        config.rings = "AAA".to_string();
+        config.plugboard = String::new();
+        config.start = "QMR".to_string();
+        let candidate = Candidate { config, score: 0.0 };
+
+        let quadgrams = NgramScorer::embedded(Language::German, 4);
+        let solved = super::refine(&letters, &candidate, 10, &quadgrams).unwrap();
+        assert!(solved.config.rings == "AAC" && solved.config.start == "QMT");
+        assert!(solved.config.plugboard == "AV BS CG DL FU HZ IN KM OW RX");
+        assert!(solved.score > quadgrams.score(&letters));
+    }
+}
@@ -0,0 +1,441 @@
+use std::fmt;
+use crate::bombe::try_letters;
+use crate::error::EnigmaError;
+use crate::rotor::{self, try_alpha_index, ALPHA};
+
+// Chance of two letters of German naval plaintext being the same, taken as
+// one in seventeen at Bletchley Park, against one in twenty-six at random
+const PLAIN_REPEAT_RATE: f64 = 1.0 / 17.0;
+const RANDOM_REPEAT_RATE: f64 = 1.0 / 26.0;
+
+// Intercepted message whose indicator has been freed of the bigram table,
+// giving the Verfahrenkenngruppe that was typed at the Grundstellung to
+// set the message key. Messages whose Kenngruppen share their first two
+// letters started with the left and middle rotors in the same place.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Intercept {
+    pub indicator: [u8; 3],
+    pub letters: Vec<u8>
+}
+
+// Placing of the second message against the first, the second starting
+// `offset` key presses after the first
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Alignment {
+    pub first: usize,
+    pub second: usize,
+    pub offset: usize,
+    pub overlap: usize,
+    pub repeats: usize,
+    pub decibans: f64
+}
+
+// Letters of one indicator place whose distances apart on the rotor have
+// been found, each with its distance from the first letter of the chain
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chain {
+    pub letters: Vec<(char, usize)>
+}
+
+// Rotor that may be on the right, with the window letter it puts against
+// each third indicator letter placed and the middle rotors left possible.
+// The decibans are those of the alignments across the middle rotor that
+// only hold if the right rotor turns over where this one does.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WheelHypothesis {
+    pub right: String,
+    pub windows: [Option<char>; 26],
+    pub middle: Vec<String>,
+    pub decibans: f64
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Analysis {
+    pub alignments: Vec<Alignment>,
+    pub chains: Vec<Chain>,
+    pub hypotheses: Vec<WheelHypothesis>,
+    pub orders: Vec<Vec<i32>>
+}
+
+// Wheels to choose from, the score an alignment needs before it is
+// believed and the fewest letters two messages must overlap by. The
+// defaults cover the eight naval wheels and odds of about 300 to 1, as
+// only the best of the 25 offsets tried for a pair is kept and a lower bar
+// lets chance repeats through.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BanburismusSpec {
+    pub rotor_pool: Vec<i32>,
+    pub threshold: f64,
+    pub min_overlap: usize
+}
+
+impl Default for BanburismusSpec {
+    fn default() -> Self {
+        Self {
+            rotor_pool: (1..=8).collect(),
+            threshold: 25.0,
+            min_overlap: 50
+        }
+    }
+}
+
+impl Intercept {
+    pub fn try_new(indicator: &str, ciphertext: &str) -> Result<Self, EnigmaError> {
+        let letters = indicator.trim().to_ascii_uppercase().chars()
+            .map(|x| try_alpha_index(x).map(|i| i as u8))
+            .collect::<Result<Vec<u8>, EnigmaError>>()?;
+
+        if letters.len() != 3 {
+            return Err(EnigmaError::MalformedIndicator(format!(
+                "expected a three letter Verfahrenkenngruppe but found '{}'", indicator.trim())));
+        }
+
+        Ok(Self { indicator: [letters[0], letters[1], letters[2]], letters: try_letters(ciphertext)? })
+    }
+}
+
+impl fmt::Display for Chain {
+    // Drawn along the rotor as on the Banbury sheets, e.g. "A..F.B"
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let length = self.letters.iter().map(|x| x.1 + 1).max().unwrap_or(0);
+        let mut scale = vec!['.'; length];
+        for &(letter, offset) in &self.letters {
+            scale[offset] = letter;
+        }
+        write!(f, "{}", scale.iter().collect::<String>())
+    }
+}
+
+// Weight of evidence, in decibans, that two messages overlapping by so
+// many letters with so many repeats are in depth rather than at random
+pub fn decibans(overlap: usize, repeats: usize) -> f64 {
+    let repeat = 10.0 * (PLAIN_REPEAT_RATE / RANDOM_REPEAT_RATE).log10();
+    let other = 10.0 * ((1.0 - PLAIN_REPEAT_RATE) / (1.0 - RANDOM_REPEAT_RATE)).log10();
+    repeats as f64 * repeat + overlap.saturating_sub(repeats) as f64 * other
+}
+
+// Overlap and repeats of two messages with the second slid along the first
+pub fn repeats(first: &[u8], second: &[u8], offset: usize) -> (usize, usize) {
+    if offset >= first.len() {
+        return (0, 0);
+    }
+
+    let overlap = second.len().min(first.len() - offset);
+    let repeats = first[offset..].iter().zip(second).filter(|(a, b)| a == b).count();
+    (overlap, repeats)
+}
+
+fn best_alignment<I: Iterator<Item = usize>>(
+    intercepts: &[Intercept], first: usize, second: usize, offsets: I, spec: &BanburismusSpec
+) -> Option<Alignment> {
+    offsets
+        .filter_map(|offset| {
+            let (overlap, repeats) = repeats(&intercepts[first].letters, &intercepts[second].letters, offset);
+            if overlap < spec.min_overlap {
+                return None;
+            }
+            Some(Alignment { first, second, offset, overlap, repeats, decibans: decibans(overlap, repeats) })
+        })
+        .filter(|x| x.decibans >= spec.threshold)
+        .max_by(|a, b| a.decibans.total_cmp(&b.decibans))
+}
+
+// Key presses in which the rotor carries its neighbour over, stepping on
+// from the given window position
+fn turnovers(notches: &[usize], start: usize, steps: usize) -> usize {
+    (0..steps).filter(|k| notches.contains(&((start + k) % 26))).count()
+}
+
+// Scritching: joins the letters of one indicator place by the distances
+// found between them, strongest evidence first, passing over any distance
+// that contradicts those already set. Links are of the form (letter of the
+// first message, letter of the second, distance) and those kept are flagged.
+fn scritch(links: &[(u8, u8, usize)]) -> (Vec<Chain>, Vec<bool>) {
+    let mut group: Vec<usize> = (0..26).collect();
+    let mut offset = [0usize; 26];
+    let mut kept = Vec::with_capacity(links.len());
+
+    for &(a, b, distance) in links {
+        let (a, b) = (a as usize, b as usize);
+        let shift = (offset[a] + distance % 26 + 26 - offset[b]) % 26;
+
+        if group[a] == group[b] {
+            kept.push(shift == 0);
+            continue;
+        }
+
+        let joined = group[a];
+        let moved: Vec<usize> = (0..26).filter(|&x| group[x] == group[b]).collect();
+        let clash = moved.iter()
+            .any(|&x| (0..26).any(|y| group[y] == joined && offset[y] == (offset[x] + shift) % 26));
+
+        if !clash {
+            for x in moved {
+                group[x] = joined;
+                offset[x] = (offset[x] + shift) % 26;
+            }
+        }
+        kept.push(!clash);
+    }
+
+    let mut chains: Vec<Chain> = Vec::new();
+    for root in 0..26 {
+        let members: Vec<usize> = (0..26).filter(|&x| group[x] == root).collect();
+        if members.len() < 2 {
+            continue;
+        }
+
+        let start = offset[members[0]];
+        let mut letters: Vec<(char, usize)> = members.iter()
+            .map(|&x| (ALPHA[x], (offset[x] + 26 - start) % 26))
+            .collect();
+        letters.sort_by_key(|x| x.1);
+        chains.push(Chain { letters });
+    }
+
+    chains.sort_by_key(|x| std::cmp::Reverse(x.letters.len()));
+    (chains, kept)
+}
+
+// Window letters given by the placings of the longest chain against the
+// alphabet that the rotor's notches allow. The indicator substitution, as
+// any Enigma substitution, pairs letters off and never leaves one in
+// place, and no turnover may fall in a stretch that two messages were
+// found in depth across. The other chains must have a placing too. With
+// no chains at all any rotor will do, and nothing is known of the windows.
+fn placings(chains: &[Chain], links: &[(u8, u8, usize)], notches: &[usize]) -> Vec<[Option<u8>; 26]> {
+    let fits = |chain: &Chain, shift: usize| {
+        let window = |x: usize| chain.letters.iter()
+            .find(|y| rotor::alpha_index(y.0) == x)
+            .map(|y| (shift + y.1) % 26);
+
+        chain.letters.iter().all(|&(letter, offset)| {
+            let x = rotor::alpha_index(letter);
+            let placed = (shift + offset) % 26;
+            placed != x && window(placed).map(|y| y == x).unwrap_or(true)
+        }) &&
+        links.iter().all(|&(a, _, distance)| match window(a as usize) {
+            Some(start) => turnovers(notches, start, distance) == 0,
+            None => true
+        })
+    };
+
+    if chains.iter().skip(1).any(|chain| !(0..26).any(|shift| fits(chain, shift))) {
+        return Vec::new();
+    }
+
+    match chains.first() {
+        None => vec![[None; 26]],
+        Some(main) => (0..26)
+            .filter(|&shift| fits(main, shift))
+            .map(|shift| {
+                let mut windows = [None; 26];
+                for &(letter, offset) in &main.letters {
+                    windows[rotor::alpha_index(letter)] = Some(((shift + offset) % 26) as u8);
+                }
+                windows
+            })
+            .collect()
+    }
+}
+
+fn try_notches(rotor_id: i32) -> Result<(String, Vec<usize>), EnigmaError> {
+    let rotor = rotor::try_rotors(rotor_id)?;
+    Ok((rotor.get_name(), rotor.get_notches().iter().map(|&x| rotor::alpha_index(x)).collect()))
+}
+
+// Slides together the messages that started with the left and middle rotors
+// in the same place, to find how far apart their right rotors were
+fn right_alignments(intercepts: &[Intercept], spec: &BanburismusSpec) -> Vec<Alignment> {
+    let mut alignments = Vec::new();
+
+    for (i, first) in intercepts.iter().enumerate() {
+        for (j, second) in intercepts.iter().enumerate().skip(i + 1) {
+            if first.indicator[..2] != second.indicator[..2] || first.indicator[2] == second.indicator[2] {
+                continue;
+            }
+
+            alignments.extend(best_alignment(intercepts, i, j, 1..26, spec));
+        }
+    }
+
+    alignments
+}
+
+// Slides together the messages that started with the left rotor in the
+// same place, given where the right rotor stood for each. The stretch
+// between them must hold a whole number of turns of the right rotor more,
+// and the number of turnovers in it is the distance between the middle
+// rotors. Links are returned with the total score of the alignments.
+fn middle_links(intercepts: &[Intercept], windows: &[Option<u8>; 26], notches: &[usize], spec: &BanburismusSpec) -> (Vec<(u8, u8, usize)>, f64) {
+    let mut alignments = Vec::new();
+
+    for (i, first) in intercepts.iter().enumerate() {
+        for (j, second) in intercepts.iter().enumerate().skip(i + 1) {
+            if first.indicator[0] != second.indicator[0] || first.indicator[1] == second.indicator[1] {
+                continue;
+            }
+
+            let (a, b) = match (windows[first.indicator[2] as usize], windows[second.indicator[2] as usize]) {
+                (Some(a), Some(b)) => (a as usize, b as usize),
+                _ => continue
+            };
+
+            let forward = (b + 26 - a) % 26;
+            let offsets = |start: usize, length: usize| (0..).map(move |k| start + 26 * k).skip_while(|&x| x == 0).take_while(move |&x| x < length);
+
+            let best = [
+                best_alignment(intercepts, i, j, offsets(forward, first.letters.len()), spec),
+                best_alignment(intercepts, j, i, offsets((26 - forward) % 26, second.letters.len()), spec)
+            ];
+
+            alignments.extend(best.iter().flatten()
+                .map(|x| (*x, turnovers(notches, if x.first == i {a} else {b}, x.offset)))
+                .filter(|x| x.1 > 0 && x.1 < 26)
+                .max_by(|x, y| x.0.decibans.total_cmp(&y.0.decibans)));
+        }
+    }
+
+    alignments.sort_by(|a, b| b.0.decibans.total_cmp(&a.0.decibans));
+
+    let links = alignments.iter()
+        .map(|(x, t)| (intercepts[x.first].indicator[1], intercepts[x.second].indicator[1], *t))
+        .collect();
+    (links, alignments.iter().map(|x| x.0.decibans).sum())
+}
+
+// Banburismus: finds messages in depth by sliding them against one
+// another, sets the distances found between their indicators on the rotor
+// and rules out the wheels whose turnovers would have broken the depths
+// found, first for the right rotor and then, for each placing of it, for
+// the middle. Indicators are taken to come from a three rotor machine.
+pub fn try_analyse(intercepts: &[Intercept], spec: &BanburismusSpec) -> Result<Analysis, EnigmaError> {
+    let wheels = spec.rotor_pool.iter()
+        .map(|&x| try_notches(x).map(|(name, notches)| (x, name, notches)))
+        .collect::<Result<Vec<(i32, String, Vec<usize>)>, EnigmaError>>()?;
+
+    let mut alignments = right_alignments(intercepts, spec);
+    alignments.sort_by(|a, b| b.decibans.total_cmp(&a.decibans));
+
+    let links: Vec<(u8, u8, usize)> = alignments.iter()
+        .map(|x| (intercepts[x.first].indicator[2], intercepts[x.second].indicator[2], x.offset))
+        .collect();
+    let (chains, kept) = scritch(&links);
+
+    let alignments: Vec<Alignment> = alignments.into_iter().zip(&kept).filter(|x| *x.1).map(|x| x.0).collect();
+    let links: Vec<(u8, u8, usize)> = links.into_iter().zip(&kept).filter(|x| *x.1).map(|x| x.0).collect();
+
+    let mut hypotheses = Vec::new();
+    let mut orders = Vec::new();
+
+    for (right, right_name, right_notches) in &wheels {
+        for windows in placings(&chains, &links, right_notches) {
+            let (middle_links, score) = middle_links(intercepts, &windows, right_notches, spec);
+            let (middle_chains, middle_kept) = scritch(&middle_links);
+            let middle_links: Vec<(u8, u8, usize)> = middle_links.into_iter().zip(&middle_kept).filter(|x| *x.1).map(|x| x.0).collect();
+
+            let middles: Vec<&(i32, String, Vec<usize>)> = wheels.iter()
+                .filter(|x| x.0 != *right && !placings(&middle_chains, &middle_links, &x.2).is_empty())
+                .collect();
+
+            for (middle, _, _) in &middles {
+                for (left, _, _) in wheels.iter().filter(|x| x.0 != *right && x.0 != *middle) {
+                    let order = vec![*left, *middle, *right];
+                    if !orders.contains(&order) {
+                        orders.push(order);
+                    }
+                }
+            }
+
+            hypotheses.push(WheelHypothesis {
+                right: right_name.clone(),
+                windows: windows.map(|x| x.map(|y| ALPHA[y as usize])),
+                middle: middles.iter().map(|x| x.1.clone()).collect(),
+                decibans: score
+            });
+        }
+    }
+
+    hypotheses.sort_by(|a, b| b.decibans.total_cmp(&a.decibans));
+
+    Ok(Analysis { alignments, chains, hypotheses, orders })
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand::distributions::{Distribution, WeightedIndex};
+    use rand::rngs::StdRng;
+    use super::{BanburismusSpec, Chain, Intercept};
+    use crate::enigma::Enigma;
+    use crate::indicator;
+    use crate::naval::{BigramTable, NavalProcedure};
+    use crate::rotor::ALPHA;
+
+    // Letters drawn with the frequencies of German, which is all that
+    // matters to the repeat rate of messages in depth
+    fn plaintext(rng: &mut StdRng, length: usize) -> String {
+        let counts: Vec<(char, u64)> = include_str!("../data/ngrams/german_unigrams.txt").lines()
+            .filter(|x| !x.starts_with('#'))
+            .filter_map(|x| x.split_once(' '))
+            .map(|(letter, count)| (letter.chars().next().unwrap(), count.parse().unwrap()))
+            .collect();
+        let letters = WeightedIndex::new(counts.iter().map(|x| x.1)).unwrap();
+
+        (0..length).map(|_| counts[letters.sample(rng)].0).collect()
+    }
+
+    #[test]
+    fn test_repeats() {
+        assert!(super::repeats(&[0, 1, 2, 3], &[2, 3, 4], 2) == (2, 2));
+        assert!(super::repeats(&[0, 1, 2, 3], &[2, 3, 4], 5) == (0, 0));
+        assert!(super::decibans(100, 10) > 0.0 && super::decibans(100, 3) < 0.0);
+        assert!(super::turnovers(&[16], 10, 7) == 1 && super::turnovers(&[16], 10, 6) == 0);
+
+        let (chains, kept) = super::scritch(&[(0, 5, 3), (5, 1, 2), (0, 1, 4), (2, 3, 7)]);
+        assert!(kept == vec![true, true, false, true]);
+        assert!(chains[0] == Chain { letters: vec![('A', 0), ('F', 3), ('B', 5)] });
+        assert!(chains[0].to_string() == "A..F.B" && chains.len() == 2);
+
+        assert!(Intercept::try_new("ABCD", "XYZ").is_err());
+        assert!(Intercept::try_new("abc", "XYZ QR").unwrap().letters.len() == 5);
+    }
+
+    #[test]
+    fn test_analyse() {
+        let mut machine = Enigma::new(vec![1, 5, 3], 'B', "M3".to_string());
+        machine.set_ring_settings("DMX");
+        machine.set_plugboard("AR BK CP DQ FU GT HZ IO");
+        let procedure = NavalProcedure { table: BigramTable::generate(1941), grundstellung: "KDV".to_string() };
+
+        // A day's traffic whose Kenngruppen all start with B, as the
+        // Kenngruppenbuch often gave the same first letters
+        let mut rng = StdRng::seed_from_u64(1941);
+        let intercepts: Vec<Intercept> = (0..60)
+            .map(|_| {
+                let kenngruppe = format!("B{}{}", ALPHA[rng.gen_range(0..6)], indicator::random_key(&mut rng, 1));
+                let mut sender = machine.clone();
+                sender.set_key(procedure.try_message_key(&machine, &kenngruppe).unwrap());
+
+                let text = plaintext(&mut rng, 400);
+                let cipher = text.chars().map(|x| sender.type_letter(x)).collect::<String>();
+                Intercept::try_new(&kenngruppe, &cipher).unwrap()
+            })
+            .collect();
+
+        let analysis = super::try_analyse(&intercepts, &BanburismusSpec::default()).unwrap();
+        assert!(!analysis.chains.is_empty() && analysis.chains[0].letters.len() > 10);
+
+        let best = &analysis.hypotheses[0];
+        assert!(analysis.hypotheses.iter().all(|x| x.right == "III"));
+        assert!(best.middle.contains(&"V".to_string()));
+
+        // The windows found are those of the message keys
+        for kenngruppe in ["BAC", "BEQ", "BDA"] {
+            let key = procedure.try_message_key(&machine, kenngruppe).unwrap();
+            let window = best.windows[crate::rotor::alpha_index(kenngruppe.chars().nth(2).unwrap())];
+            assert!(window == key.chars().last());
+        }
+        assert!(analysis.orders.contains(&vec![1, 5, 3]) && analysis.orders.len() < 8 * 7 * 6 / 4);
+    }
+}
@@ -0,0 +1,398 @@
+use crate::error::EnigmaError;
+use crate::reflector::{self, Reflector};
+use crate::rotor::{self, try_alpha_index, Rotor, ALPHA};
+use crate::text;
+
+// One pairing of a crib letter with the ciphertext letter at the same
+// place, position counting from the start of the message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MenuLink {
+    pub position: usize,
+    pub plain: char,
+    pub cipher: char
+}
+
+// Letter graph of a crib laid against the ciphertext, each link joining a
+// plain and cipher letter through the scrambler at the link's position
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Menu {
+    pub offset: usize,
+    pub links: Vec<MenuLink>
+}
+
+// Machine settings at which the bombe stopped, with the plugboard
+// connections implied for the letters of the menu. A letter paired with
+// itself is one left without a cable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stop {
+    pub rotors: Vec<String>,
+    pub reflector: char,
+    pub start: String,
+    pub steckers: Vec<(char, char)>
+}
+
+pub(crate) fn try_letters(text: &str) -> Result<Vec<u8>, EnigmaError> {
+    text::strip_groups(text).chars()
+        .map(|x| try_alpha_index(x.to_ascii_uppercase()).map(|i| i as u8))
+        .collect()
+}
+
+// First place at which the crib would have a letter enciphered to itself,
+// which the reflector makes impossible
+pub(crate) fn find_crash(cipher: &[u8], crib: &[u8], offset: usize) -> Option<usize> {
+    crib.iter().zip(&cipher[offset..]).position(|(a, b)| a == b).map(|i| offset + i)
+}
+
+impl Menu {
+    pub fn try_build(ciphertext: &str, crib: &str, offset: usize) -> Result<Self, EnigmaError> {
+        let cipher = try_letters(ciphertext)?;
+        let crib = try_letters(crib)?;
+
+        if crib.is_empty() || offset + crib.len() > cipher.len() {
+            return Err(EnigmaError::InvalidCrib(format!(
+                "crib of {} letters at offset {} does not fit a ciphertext of {} letters", crib.len(), offset, cipher.len())));
+        }
+
+        if let Some(position) = find_crash(&cipher, &crib, offset) {
+            return Err(EnigmaError::CribCrash { position, letter: ALPHA[cipher[position] as usize] });
+        }
+
+        Ok(Self::from_letters(&cipher, &crib, offset))
+    }
+
+    // Menu of a crib already known to fit without a crash
+    pub(crate) fn from_letters(cipher: &[u8], crib: &[u8], offset: usize) -> Self {
+        let links = crib.iter().zip(&cipher[offset..]).enumerate()
+            .map(|(i, (&p, &c))| MenuLink { position: offset + i, plain: ALPHA[p as usize], cipher: ALPHA[c as usize] })
+            .collect();
+
+        Self { offset, links }
+    }
+
+    pub fn letters(&self) -> Vec<char> {
+        let mut letters: Vec<char> = self.links.iter().flat_map(|x| [x.plain, x.cipher]).collect();
+        letters.sort();
+        letters.dedup();
+        letters
+    }
+
+    pub fn connections(&self, letter: char) -> usize {
+        self.links.iter().filter(|x| x.plain == letter).count()
+            + self.links.iter().filter(|x| x.cipher == letter).count()
+    }
+
+    // The most connected letter, on which the test register is placed
+    pub fn central_letter(&self) -> Option<char> {
+        let letters = self.letters();
+        let mut central = *letters.first()?;
+
+        for &letter in &letters {
+            if self.connections(letter) > self.connections(central) {
+                central = letter;
+            }
+        }
+
+        Some(central)
+    }
+
+    // Number of separate pieces the menu falls into
+    pub fn components(&self) -> usize {
+        let mut parent: Vec<usize> = (0..26).collect();
+
+        fn root(parent: &mut [usize], x: usize) -> usize {
+            let mut x = x;
+            while parent[x] != x {
+                parent[x] = parent[parent[x]];
+                x = parent[x];
+            }
+            x
+        }
+
+        for link in &self.links {
+            let a = root(&mut parent, rotor::alpha_index(link.plain));
+            let b = root(&mut parent, rotor::alpha_index(link.cipher));
+            parent[a] = b;
+        }
+
+        self.letters().iter().filter(|&&x| root(&mut parent, rotor::alpha_index(x)) == rotor::alpha_index(x)).count()
+    }
+
+    // Independent closed loops in the menu, each of which lets the bombe
+    // reject most wrong settings on its own
+    pub fn loops(&self) -> usize {
+        self.links.len() + self.components() - self.letters().len()
+    }
+}
+
+// Rotors and reflector of one drum of the bombe, wired as the Enigma
+// rotors but with no plugboard
+pub(crate) struct Scrambler {
+    rotors: Vec<Rotor>,
+    reflector: Reflector
+}
+
+impl Scrambler {
+    // Checks the wheel order as the Enigma does, the fourth rotor of an M4
+    // being the only place for a Greek wheel
+    pub(crate) fn try_new(rotor_ids: &[i32], reflector: char) -> Result<Self, EnigmaError> {
+        let (model, labels) = match rotor_ids.len() {
+            3 => ("M3", vec!["left", "middle", "right"]),
+            4 => ("M4", vec!["left", "middle left", "middle right", "right"]),
+            n => return Err(EnigmaError::WrongRotorCount { model: "M3".to_string(), expected: 3, found: n })
+        };
+
+        let mut rotors = Vec::<Rotor>::with_capacity(rotor_ids.len());
+
+        for (i, (label, rotor_id)) in labels.iter().zip(rotor_ids).enumerate() {
+            if rotor_ids[..i].contains(rotor_id) {
+                return Err(EnigmaError::DuplicateRotor(*rotor_id));
+            }
+            let rotor = rotor::try_rotors(*rotor_id)?;
+
+            if rotor.is_greek() != (model == "M4" && i == 0) {
+                return Err(EnigmaError::MisplacedRotor { rotor: *rotor_id, position: label.to_string() });
+            }
+
+            rotors.push(rotor);
+        }
+
+        let reflector = if model == "M4" {
+            reflector::try_thin_reflectors(reflector).map_err(|e| match reflector::try_reflectors(reflector) {
+                Ok(_) => EnigmaError::IncompatibleReflector { model: model.to_string(), reflector },
+                Err(_) => e
+            })?
+        }
+        else {
+            reflector::try_reflectors(reflector)?
+        };
+
+        Ok(Self { rotors, reflector })
+    }
+
+    pub(crate) fn set_start(&mut self, index: usize) {
+        let n_rotors = self.rotors.len();
+        for (i, rotor) in self.rotors.iter_mut().enumerate() {
+            rotor.set_position(((index / 26usize.pow((n_rotors - 1 - i) as u32)) % 26) as u8);
+        }
+    }
+
+    // Turns the right rotor alone, leaving out any turnover
+    pub(crate) fn step_right(&mut self) {
+        if let Some(rotor) = self.rotors.last_mut() {
+            rotor.rotate(None);
+        }
+    }
+
+    pub(crate) fn get_key(&self) -> String {
+        self.rotors.iter().map(|x| x.get_face_letter()).collect()
+    }
+
+    pub(crate) fn table(&self) -> [u8; 26] {
+        let mut table = [0u8; 26];
+
+        for (x, out) in table.iter_mut().enumerate() {
+            let mut cipher = x as u8;
+            for rotor in self.rotors.iter().rev() {
+                cipher = rotor.forward(cipher);
+            }
+            cipher = self.reflector.reflect(cipher);
+            for rotor in self.rotors.iter() {
+                cipher = rotor.backward(cipher);
+            }
+            *out = cipher;
+        }
+
+        table
+    }
+}
+
+// Live wires of the diagonal board, as a mask of the letters y for which
+// "x is steckered to y" holds for each letter x, reached from a single
+// hypothesis for the central letter
+fn energise(links: &[(u8, u8)], tables: &[[u8; 26]], centre: u8, test: u8) -> [u32; 26] {
+    let mut live = [0u32; 26];
+    let mut pending = vec![(centre, test)];
+
+    while let Some((x, y)) = pending.pop() {
+        if live[x as usize] & (1 << y) != 0 {
+            continue;
+        }
+        live[x as usize] |= 1 << y;
+
+        // Steckering is reciprocal, which the diagonal board wires in
+        pending.push((y, x));
+
+        for (&(a, b), table) in links.iter().zip(tables) {
+            if a == x {
+                pending.push((b, table[y as usize]));
+            }
+            if b == x {
+                pending.push((a, table[y as usize]));
+            }
+        }
+    }
+
+    live
+}
+
+// Plugboard connections implied by a consistent hypothesis, or none when a
+// letter is driven to two partners at once. Letters off the menu are only
+// reached as partners through the diagonal board, but two menu letters
+// sharing one of them is as much a contradiction.
+fn implied_steckers(live: &[u32; 26], letters: &[u8]) -> Option<Vec<(char, char)>> {
+    if live.iter().any(|x| x.count_ones() > 1) {
+        return None;
+    }
+
+    let mut steckers = Vec::new();
+
+    for &x in letters {
+        if live[x as usize].count_ones() != 1 {
+            return None;
+        }
+        let y = live[x as usize].trailing_zeros() as u8;
+        steckers.push(if x <= y {(ALPHA[x as usize], ALPHA[y as usize])} else {(ALPHA[y as usize], ALPHA[x as usize])});
+    }
+
+    steckers.sort();
+    steckers.dedup();
+    Some(steckers)
+}
+
+// Runs the menu through every start position of one wheel order with the
+// rings at A. A stop is made when the test register on the central letter
+// shows one live wire, the hypothesis tested being consistent, or all but
+// one, the remaining wire being the only consistent hypothesis. Stops whose
+// implied steckers contradict one another are discarded, as they would have
+// been by the checking of the operators.
+//
+// As on the real bombe only the right rotor moves along the menu, since
+// the turnover depends on the ring settings being sought. The start found
+// is that of the rotor cores, the key less the rings, and a menu spanning
+// a turnover of the middle rotor will miss the right stop.
+pub fn try_run(menu: &Menu, rotors: &[i32], reflector: char) -> Result<Vec<Stop>, EnigmaError> {
+    let mut scrambler = Scrambler::try_new(rotors, reflector)?;
+    let rotor_names: Vec<String> = scrambler.rotors.iter().map(|x| x.get_name()).collect();
+
+    let centre = match menu.central_letter() {
+        Some(c) => rotor::alpha_index(c) as u8,
+        None => return Ok(Vec::new())
+    };
+    let letters: Vec<u8> = menu.letters().iter().map(|&x| rotor::alpha_index(x) as u8).collect();
+    let links: Vec<(u8, u8)> = menu.links.iter()
+        .map(|x| (rotor::alpha_index(x.plain) as u8, rotor::alpha_index(x.cipher) as u8))
+        .collect();
+    let last_position = menu.links.iter().map(|x| x.position).max().unwrap_or(0);
+
+    let mut tables = vec![[0u8; 26]; links.len()];
+    let mut stops = Vec::new();
+
+    for index in 0..26usize.pow(rotors.len() as u32) {
+        scrambler.set_start(index);
+        let start = scrambler.get_key();
+
+        // The letter at each position is enciphered after the key press stepping the rotors
+        let mut link = 0;
+        for position in 0..=last_position {
+            scrambler.step_right();
+            while link < links.len() && menu.links[link].position == position {
+                tables[link] = scrambler.table();
+                link += 1;
+            }
+        }
+
+        let live = energise(&links, &tables, centre, 0);
+        let hypothesis = match live[centre as usize].count_ones() {
+            1 => live,
+            25 => energise(&links, &tables, centre, (!live[centre as usize] & ((1 << 26) - 1)).trailing_zeros() as u8),
+            _ => continue
+        };
+
+        if let Some(steckers) = implied_steckers(&hypothesis, &letters) {
+            stops.push(Stop { rotors: rotor_names.clone(), reflector, start, steckers });
+        }
+    }
+
+    Ok(stops)
+}
+
+pub fn try_run_orders(menu: &Menu, orders: &[Vec<i32>], reflector: char) -> Result<Vec<Stop>, EnigmaError> {
+    let mut stops = Vec::new();
+    for order in orders {
+        stops.extend(try_run(menu, order, reflector)?);
+    }
+    Ok(stops)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Menu;
+    use crate::enigma::Enigma;
+    use crate::error::EnigmaError;
+    use crate::rotor::BETA;
+
+    const PLAINTEXT: &str = "WETTERVORHERSAGEBISKAYAXSTURMAUSWESTXSEEGANGSECHS";
+    const CRIB: &str = "WETTERVORHERSAGEBISKAYA";
+
+    const PLUGBOARD: &str = "AV BS CG DL FU HZ IN KM OW RX";
+
+    // The right ring at C, giving cores at KEA, leaves the crib clear of
+    // the turnover at Z
+    fn cipher() -> String {
+        let mut machine = Enigma::new(vec![2, 4, 5], 'B', "M3".to_string());
+        machine.set_plugboard(PLUGBOARD);
+        machine.set_ring_settings("AAC");
+        machine.set_key("KEC".to_string());
+        machine.type_phrase(PLAINTEXT.to_string())
+    }
+
+    #[test]
+    fn test_menu() {
+        let menu = Menu::try_build("ABCDE FGHIJ", "CDA", 1).unwrap();
+        assert!(menu.links.len() == 3 && menu.links[0].position == 1 && menu.links[0].plain == 'C');
+        assert!(menu.letters() == vec!['A', 'B', 'C', 'D']);
+        assert!(menu.central_letter() == Some('C') && menu.connections('B') == 1);
+        assert!(menu.components() == 1 && menu.loops() == 0);
+
+        // Links B-C and C-B close a loop through two scramblers
+        let menu = Menu::try_build("CBXYZ", "BC", 0).unwrap();
+        assert!(menu.loops() == 1);
+
+        assert!(Menu::try_build("ABCDE", "XBZ", 0) == Err(EnigmaError::CribCrash { position: 1, letter: 'B' }));
+        assert!(matches!(Menu::try_build("ABCDE", "XYZ", 3), Err(EnigmaError::InvalidCrib(_))));
+        assert!(Menu::try_build("ABCDE", "X1Z", 0).is_err());
+    }
+
+    #[test]
+    fn test_bombe_stop() {
+        let menu = Menu::try_build(&cipher(), CRIB, 0).unwrap();
+        assert!(menu.loops() >= 2);
+
+        let stops = super::try_run(&menu, &[2, 4, 5], 'B').unwrap();
+        let plugboard = crate::plugboard::Plugboard::from_pairs(PLUGBOARD);
+
+        let stop = stops.iter().find(|x| x.start == "KEA").unwrap();
+        assert!(stop.rotors == vec!["II", "IV", "V"] && stop.reflector == 'B');
+        assert!(stop.steckers.iter().all(|&(a, b)| plugboard.convert(a) == b));
+        assert!(stop.steckers.contains(&('H', 'Z')) && stop.steckers.contains(&('E', 'E')));
+
+        assert!(super::try_run(&menu, &[2, 2, 5], 'B') == Err(EnigmaError::DuplicateRotor(2)));
+        assert!(super::try_run(&menu, &[9, 2, 5], 'B').is_err());
+        assert!(super::try_run(&menu, &[1, 2], 'B').is_err());
+        assert!(matches!(super::try_run(&menu, &[2, 4, 5, 1], 'B'), Err(EnigmaError::MisplacedRotor { rotor: 2, .. })));
+        assert!(super::try_run(&menu, &[BETA, 2, 4, 5], 'A') == Err(EnigmaError::IncompatibleReflector { model: "M4".to_string(), reflector: 'A' }));
+    }
+
+    #[test]
+    fn test_stecker_clash() {
+        // Scramblers swapping neighbouring letters send A to Z, C to Y and
+        // B back to Z, so A and B both claim Z off the menu
+        let table: [u8; 26] = std::array::from_fn(|x| (x ^ 1) as u8);
+        let live = super::energise(&[(0, 2), (2, 1)], &[table, table], 0, 25);
+        assert!(live[0] == 1 << 25 && live[1] == 1 << 25 && live[2] == 1 << 24);
+        assert!(super::implied_steckers(&live, &[0, 1, 2]).is_none());
+
+        let live = super::energise(&[(0, 2)], &[table], 0, 25);
+        assert!(super::implied_steckers(&live, &[0, 2]) == Some(vec![('A', 'Z'), ('C', 'Y')]));
+    }
+}
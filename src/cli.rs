@@ -0,0 +1,725 @@
+use std::fs;
+use std::io::{Read, Write};
+use enigma::{text, AttackSpec, Enigma, EnigmaError, InputPolicy, Language, KeySheet, KeySheetSpec, MachineConfig, RadioMessage};
+use enigma::indicator::{self, Indicator, Procedure};
+use enigma::text::{OutputFormat, Padding};
+use enigma::rotor::try_rotor_id;
+
+pub const USAGE: &str = "Usage: enigma <encrypt|decrypt|repl|keysheet|attack|crib> [OPTIONS]
+
+Options:
+    --config <FILE>          Load the machine setup from a text or JSON configuration file,
+                             in place of the model, rotor, ring, plugboard and start options
+    --keysheet <FILE>        Set up the machine from a key sheet file for the day given by --date
+    --date <YYYY-MM-DD>      Day of the key sheet to use, or the month to generate (YYYY-MM)
+    --model <M3|M4>          Enigma variant [default: M3, or M4 when four rotors are given]
+    --rotors <LIST>          Rotors from left to right, e.g. II,IV,V or Beta,5,6,8 [default: I,II,III]
+    --reflector <B|C|A>      Reflector type [default: B]
+    --rings <SETTING>        Ring settings as letters (BUL) or numbers (02,21,12) [default: all A]
+    --plugboard <PAIRS>      Plugboard pairs, e.g. \"AV BS CG\" [default: none]
+    --start <KEY>            Start position of the rotors, e.g. BLA [default: all A]
+    --indicator <PROCEDURE>  Send a fresh message key with each message using the doubled
+                             (pre-1940) or single (post-1940) indicator procedure
+    --grundstellung <KEY>    Ground setting for the doubled procedure, or the one to use in
+                             place of a random choice for the single procedure
+    --radio                  Write or read a complete radio message, with a preamble for each
+                             part of at most 250 letters, using the --indicator procedure
+    --time <HHMM>            Time of origin for the radio message preamble [default: now, UTC]
+    --policy <POLICY>        Handling of characters other than letters: reject, drop, pass
+                             or transliterate [default: reject]
+    --group <SIZE>           Letters per group in the output, 0 for no grouping [default: 5]
+    --padding <PADDING>      Completion of the last group: none, a filler letter, random
+                             or random:<seed> [default: X]
+    --pretty                 Undo the transliteration conventions when decrypting
+    --seed <NUMBER>          Seed for generating a key sheet [default: random]
+    --pool <LIST>            Rotors to draw the key sheet wheel orders from, or to search in an
+                             attack [default: I-V, or I-VIII for M4]
+    --candidates <NUMBER>    Rotor settings taken forward from the wheel order search [default: 10]
+    --plugs <NUMBER>         Most plugboard cables to look for in an attack [default: 10]
+    --score <SCORER>         Scoring of attack decryptions: ioc, unigram, bigram, trigram
+                             or quadgram [default: ioc]
+    --language <LANGUAGE>    Language of the n-gram scorers: german or english [default: german]
+    --crib <TEXT>            Probable plaintext to slide along the ciphertext, may be repeated
+    -i, --input <FILE>       Read text from a file instead of stdin
+    -o, --output <FILE>      Write text to a file instead of stdout
+    -h, --help               Print this message
+
+The keysheet command prints a month of daily keys for --date, and with --output also
+writes them to a file for use with --keysheet.
+
+The attack command reads ciphertext alone and searches the wheel orders of --pool with the
+--model and --reflector given, printing the most likely machine settings found.
+
+The crib command reads ciphertext and lists the places each --crib can take, where no letter
+would be enciphered to itself, with the menu each gives the bombe, those with most loops first.
+
+Run without arguments for an interactive session with the default machine.";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Encrypt,
+    Decrypt,
+    Repl,
+    KeySheet,
+    Attack,
+    Crib,
+    Help
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Options {
+    pub command: Command,
+    pub config: Option<String>,
+    pub keysheet: Option<String>,
+    pub date: Option<String>,
+    pub seed: Option<u64>,
+    pub pool: Option<Vec<i32>>,
+    pub candidates: Option<usize>,
+    pub plugs: Option<usize>,
+    pub score: String,
+    pub language: Language,
+    pub cribs: Vec<String>,
+    pub model: Option<String>,
+    pub rotors: Vec<i32>,
+    pub reflector: char,
+    pub rings: Option<String>,
+    pub plugboard: String,
+    pub start: Option<String>,
+    pub indicator: Option<Procedure>,
+    pub grundstellung: Option<String>,
+    pub radio: bool,
+    pub time: Option<String>,
+    pub policy: InputPolicy,
+    pub pretty: bool,
+    pub format: OutputFormat,
+    pub input: Option<String>,
+    pub output: Option<String>
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            command: Command::Help,
+            config: None,
+            keysheet: None,
+            date: None,
+            seed: None,
+            pool: None,
+            candidates: None,
+            plugs: None,
+            score: "ioc".to_string(),
+            language: Language::German,
+            cribs: Vec::new(),
+            model: None,
+            rotors: vec![1, 2, 3],
+            reflector: 'B',
+            rings: None,
+            plugboard: String::new(),
+            start: None,
+            indicator: None,
+            grundstellung: None,
+            radio: false,
+            time: None,
+            policy: InputPolicy::default(),
+            pretty: false,
+            format: OutputFormat::default(),
+            input: None,
+            output: None
+        }
+    }
+}
+
+fn parse_rotors(value: &str) -> Result<Vec<i32>, String> {
+    value.split([',', ' '])
+        .filter(|x| !x.is_empty())
+        .map(|x| try_rotor_id(x).map_err(|e| e.to_string()))
+        .collect()
+}
+
+pub fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args_iter = args.iter();
+
+    options.command = match args_iter.next().map(|x| x.as_str()) {
+        Some("encrypt") => Command::Encrypt,
+        Some("decrypt") => Command::Decrypt,
+        Some("repl") => Command::Repl,
+        Some("keysheet") => Command::KeySheet,
+        Some("attack") => Command::Attack,
+        Some("crib") => Command::Crib,
+        Some("help") | Some("-h") | Some("--help") | None => return Ok(options),
+        Some(other) => return Err(format!("Unrecognised command '{}'", other))
+    };
+
+    while let Some(arg) = args_iter.next() {
+        let (flag, inline_value) = match arg.split_once('=') {
+            Some((f, v)) if f.starts_with("--") => (f, Some(v.to_string())),
+            _ => (arg.as_str(), None)
+        };
+
+        if flag == "-h" || flag == "--help" {
+            options.command = Command::Help;
+            return Ok(options);
+        }
+
+        // Switches which take no value
+        match flag {
+            "--pretty" => {
+                options.pretty = true;
+                continue;
+            },
+            "--radio" => {
+                options.radio = true;
+                continue;
+            },
+            _ => ()
+        }
+
+        let value = match inline_value {
+            Some(v) => v,
+            None => match args_iter.next() {
+                Some(v) => v.clone(),
+                None => return Err(format!("Missing value for option '{}'", flag))
+            }
+        };
+
+        match flag {
+            "--config" => options.config = Some(value),
+            "--keysheet" => options.keysheet = Some(value),
+            "--date" => options.date = Some(value),
+            "--seed" => options.seed = Some(value.parse().map_err(|_| format!("Invalid seed '{}'", value))?),
+            "--pool" => options.pool = Some(parse_rotors(&value)?),
+            "--candidates" => options.candidates = Some(value.parse()
+                .map_err(|_| format!("Invalid number of candidates '{}'", value))?),
+            "--plugs" => options.plugs = Some(value.parse()
+                .map_err(|_| format!("Invalid number of plugs '{}'", value))?),
+            "--score" => options.score = value,
+            "--language" => options.language = value.parse()?,
+            "--crib" => options.cribs.push(value),
+            "--model" => options.model = Some(value.to_ascii_uppercase()),
+            "--rotors" => options.rotors = parse_rotors(&value)?,
+            "--reflector" => {
+                let letters: Vec<char> = value.to_ascii_uppercase().chars().collect();
+                if letters.len() != 1 {
+                    return Err(format!("Invalid reflector '{}'", value));
+                }
+                options.reflector = letters[0];
+            },
+            "--rings" => options.rings = Some(value),
+            "--plugboard" => options.plugboard = value,
+            "--start" => options.start = Some(value),
+            "--indicator" => options.indicator = Some(value.parse()?),
+            "--grundstellung" => options.grundstellung = Some(value),
+            "--time" => options.time = Some(value),
+            "--policy" => options.policy = value.parse()?,
+            "--group" => options.format.group_size = value.parse()
+                .map_err(|_| format!("Invalid group size '{}'", value))?,
+            "--padding" => options.format.padding = value.parse::<Padding>()?,
+            "-i" | "--input" => options.input = Some(value),
+            "-o" | "--output" => options.output = Some(value),
+            _ => return Err(format!("Unrecognised option '{}'", flag))
+        }
+    }
+
+    Ok(options)
+}
+
+// Dates are given as YYYY-MM-DD, or YYYY-MM when only the month is needed
+fn parse_date(value: &str) -> Result<(u32, u32, Option<u32>), String> {
+    let parts: Vec<&str> = value.split('-').collect();
+    let invalid = || format!("Invalid date '{}', expected YYYY-MM-DD", value);
+
+    if parts.len() < 2 || parts.len() > 3 {
+        return Err(invalid());
+    }
+
+    let numbers = parts.iter()
+        .map(|x| x.parse::<u32>().map_err(|_| invalid()))
+        .collect::<Result<Vec<u32>, String>>()?;
+
+    Ok((numbers[0], numbers[1], numbers.get(2).cloned()))
+}
+
+fn read_file(path: &str) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|e| format!("Failed to read '{}': {}", path, e))
+}
+
+fn build_from_keysheet(path: &str, options: &Options) -> Result<Enigma, String> {
+    let sheet = KeySheet::try_from_json(&read_file(path)?).map_err(|e| e.to_string())?;
+
+    let (year, month, day) = match options.date.as_deref().map(parse_date) {
+        Some(Ok((year, month, Some(day)))) => (year, month, day),
+        Some(Err(e)) => return Err(e),
+        _ => return Err("A key sheet requires the day to use with --date YYYY-MM-DD".to_string())
+    };
+
+    let mut machine = sheet.try_machine_for(year, month, day).map_err(|e| e.to_string())?;
+
+    if let Some(start) = &options.start {
+        machine.try_set_key(start.clone()).map_err(|e| e.to_string())?;
+    }
+
+    Ok(machine)
+}
+
+fn rotor_pool(options: &Options, model: &str) -> Vec<i32> {
+    match &options.pool {
+        Some(p) => p.clone(),
+        None => if model == "M4" {(1..=8).collect()} else {(1..=5).collect()}
+    }
+}
+
+pub fn generate_keysheet(options: &Options) -> Result<KeySheet, String> {
+    let (year, month) = match options.date.as_deref().map(parse_date) {
+        Some(Ok((year, month, _))) => (year, month),
+        Some(Err(e)) => return Err(e),
+        None => return Err("A key sheet requires the month to generate with --date YYYY-MM".to_string())
+    };
+
+    let model = options.model.clone().unwrap_or_else(|| "M3".to_string());
+    let pool = rotor_pool(options, &model);
+
+    let spec = KeySheetSpec { model, reflector: options.reflector, rotor_pool: pool, ..KeySheetSpec::default() };
+    let seed = options.seed.unwrap_or_else(rand::random);
+
+    KeySheet::try_generate(year, month, seed, &spec).map_err(|e| e.to_string())
+}
+
+pub fn attack_spec(options: &Options) -> AttackSpec {
+    let defaults = AttackSpec::default();
+    let model = options.model.clone().unwrap_or(defaults.model);
+
+    AttackSpec {
+        rotor_pool: rotor_pool(options, &model),
+        model,
+        reflectors: vec![options.reflector],
+        candidates: options.candidates.unwrap_or(defaults.candidates),
+        max_plugs: options.plugs.unwrap_or(defaults.max_plugs)
+    }
+}
+
+// Ranked table of the settings found, each with the start of its decipherment
+pub fn process_attack(options: &Options, ciphertext: &str) -> Result<String, String> {
+    let scorer = enigma::scoring::try_scorer(&options.score, options.language).map_err(|e| e.to_string())?;
+    let ranked = enigma::attack::try_attack_with(ciphertext, &attack_spec(options), scorer.as_ref()).map_err(|e| e.to_string())?;
+    let mut lines = Vec::new();
+
+    for (i, candidate) in ranked.iter().enumerate() {
+        let config = &candidate.config;
+        let mut machine = Enigma::try_from_config(config).map_err(|e| e.to_string())?;
+        let plain: String = machine.try_decode_phrase(ciphertext).map_err(|e| e.to_string())?.chars().take(40).collect();
+
+        lines.push(format!(
+            "{:>2}. {:.4}  {} {}  rings {}  start {}  plugboard {}\n    {}",
+            i + 1, candidate.score, config.rotors.join(" "), config.reflector, config.rings, config.start,
+            if config.plugboard.is_empty() {"-"} else {&config.plugboard}, plain));
+    }
+
+    Ok(lines.join("\n"))
+}
+
+// Number of places left to each crib, then the placements in order of the
+// menus they give
+pub fn process_cribs(options: &Options, ciphertext: &str) -> Result<String, String> {
+    if options.cribs.is_empty() {
+        return Err("The crib command requires at least one --crib".to_string());
+    }
+
+    let placements = enigma::crib::try_drag(ciphertext, &options.cribs).map_err(|e| e.to_string())?;
+    let length = text::strip_groups(ciphertext).len();
+    let mut lines = Vec::new();
+
+    for crib in &options.cribs {
+        let crib = text::strip_groups(crib).to_ascii_uppercase();
+        let admissible = placements.iter().filter(|x| x.crib == crib).count();
+        lines.push(format!("{}: {} of {} positions admissible", crib, admissible, enigma::crib::positions(length, crib.len())));
+    }
+
+    lines.push(String::new());
+
+    for (i, placement) in placements.iter().enumerate() {
+        lines.push(format!(
+            "{:>3}. {} at {}  links {}  letters {}  loops {}  components {}  centre {}",
+            i + 1, placement.crib, placement.get_offset(), placement.menu.links.len(), placement.letters,
+            placement.loops, placement.components,
+            placement.central_letter.map(|x| format!("{} ({})", x, placement.connections)).unwrap_or_default()));
+    }
+
+    Ok(lines.join("\n"))
+}
+
+pub fn build_machine(options: &Options) -> Result<Enigma, String> {
+    let mut machine = match (&options.config, &options.keysheet) {
+        (Some(path), _) => {
+            let config = MachineConfig::try_parse(&read_file(path)?).map_err(|e| e.to_string())?;
+            Enigma::try_from_config(&config).map_err(|e| e.to_string())?
+        },
+        (None, Some(path)) => build_from_keysheet(path, options)?,
+        (None, None) => build_from_options(options)?
+    };
+
+    machine.set_input_policy(options.policy);
+    machine.set_output_format(options.format);
+
+    Ok(machine)
+}
+
+fn build_from_options(options: &Options) -> Result<Enigma, String> {
+    let model = match &options.model {
+        Some(m) => m.clone(),
+        None => if options.rotors.len() == 4 {"M4".to_string()} else {"M3".to_string()}
+    };
+
+    let mut machine = Enigma::try_new(options.rotors.clone(), options.reflector, model)
+        .map_err(|e| e.to_string())?;
+
+    if let Some(rings) = &options.rings {
+        machine.try_set_ring_settings(rings).map_err(|e| e.to_string())?;
+    }
+
+    machine.try_set_plugboard(&options.plugboard).map_err(|e| e.to_string())?;
+
+    if let Some(start) = &options.start {
+        machine.try_set_key(start.clone()).map_err(|e| e.to_string())?;
+    }
+
+    Ok(machine)
+}
+
+pub fn process(machine: &mut Enigma, command: &Command, message: &str) -> Result<String, EnigmaError> {
+    match command {
+        Command::Encrypt => machine.try_type_phrase(message.to_string()),
+        _ => machine.try_decode_phrase(message)
+    }
+}
+
+// With an indicator procedure the message key is drawn afresh for each
+// message and its indicator written on the first line of the output, where
+// it is read back from when decrypting
+pub fn process_with_indicator(machine: &Enigma, options: &Options, procedure: Procedure, message: &str) -> Result<String, String> {
+    let grundstellung = options.grundstellung.as_deref();
+
+    if options.command == Command::Encrypt {
+        let (indicator, cipher) = indicator::try_encrypt_message(
+            machine, procedure, grundstellung, message, &mut rand::thread_rng()).map_err(|e| e.to_string())?;
+        return Ok(format!("{}\n{}", indicator, cipher));
+    }
+
+    let (header, body) = message.trim_start().split_once('\n').unwrap_or((message.trim(), ""));
+    let indicator = Indicator::try_parse(header, procedure, grundstellung).map_err(|e| e.to_string())?;
+
+    indicator::try_decrypt_message(machine, &indicator, body).map_err(|e| e.to_string())
+}
+
+fn current_time() -> String {
+    let seconds = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|x| x.as_secs())
+        .unwrap_or(0);
+
+    format!("{:02}{:02}", (seconds / 3600) % 24, (seconds / 60) % 60)
+}
+
+pub fn process_radio(machine: &Enigma, options: &Options, message: &str) -> Result<String, String> {
+    let procedure = options.indicator.unwrap_or(Procedure::Single);
+    let grundstellung = options.grundstellung.as_deref();
+
+    if options.command == Command::Encrypt {
+        let time = options.time.clone().unwrap_or_else(current_time);
+        let radio = RadioMessage::try_compose(machine, procedure, grundstellung, &time, message, &mut rand::thread_rng())
+            .map_err(|e| e.to_string())?;
+        return Ok(radio.to_string());
+    }
+
+    RadioMessage::try_parse(message, procedure, grundstellung)
+        .and_then(|x| x.try_decrypt(machine))
+        .map_err(|e| e.to_string())
+}
+
+pub fn run(options: &Options) -> Result<(), String> {
+    if options.command == Command::Help {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+
+    if options.command == Command::KeySheet {
+        let sheet = generate_keysheet(options)?;
+        if let Some(path) = &options.output {
+            fs::write(path, sheet.to_json()).map_err(|e| format!("Failed to write '{}': {}", path, e))?;
+        }
+        print!("{}", sheet.to_table());
+        return Ok(());
+    }
+
+    if options.command == Command::Attack {
+        let text = read_input(options)?;
+        return write_output(options, &process_attack(options, &text)?);
+    }
+
+    if options.command == Command::Crib {
+        let text = read_input(options)?;
+        return write_output(options, &process_cribs(options, &text)?);
+    }
+
+    let mut machine = build_machine(options)?;
+
+    if options.command == Command::Repl {
+        return crate::repl::run(machine);
+    }
+
+    let text = read_input(options)?;
+
+    let mut output = match options.indicator {
+        _ if options.radio => process_radio(&machine, options, &text)?,
+        Some(procedure) => process_with_indicator(&machine, options, procedure, &text)?,
+        None => process(&mut machine, &options.command, &text).map_err(|e| e.to_string())?
+    };
+
+    if options.pretty && options.command == Command::Decrypt {
+        output = text::prettify(&output);
+    }
+
+    write_output(options, &output)
+}
+
+fn read_input(options: &Options) -> Result<String, String> {
+    match &options.input {
+        Some(path) => fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read '{}': {}", path, e)),
+        None => {
+            let mut buffer = String::new();
+            std::io::stdin().read_to_string(&mut buffer)
+                .map_err(|e| format!("Failed to read from stdin: {}", e))?;
+            Ok(buffer)
+        }
+    }
+}
+
+fn write_output(options: &Options, output: &str) -> Result<(), String> {
+    match &options.output {
+        Some(path) => fs::write(path, format!("{}\n", output))
+            .map_err(|e| format!("Failed to write '{}': {}", path, e)),
+        None => writeln!(std::io::stdout(), "{}", output)
+            .map_err(|e| format!("Failed to write to stdout: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Command, Options, OutputFormat, Padding, Procedure};
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(|x| x.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let options = super::parse_args(&args(
+            "encrypt --rotors II,IV,V --reflector b --rings=02,21,12 --start BLA -o out.txt")).unwrap();
+
+        assert!(options == Options {
+            command: Command::Encrypt,
+            rotors: vec![2, 4, 5],
+            reflector: 'B',
+            rings: Some("02,21,12".to_string()),
+            start: Some("BLA".to_string()),
+            output: Some("out.txt".to_string()),
+            ..Options::default()
+        });
+
+        assert!(super::parse_args(&args("decrypt --help")).unwrap().command == Command::Help);
+        assert!(super::parse_args(&args("scramble")).is_err());
+        assert!(super::parse_args(&args("encrypt --rotors I,II,IX")).is_err());
+        assert!(super::parse_args(&args("encrypt --start")).is_err());
+        assert!(super::parse_args(&args("encrypt --colour red")).is_err());
+    }
+
+    #[test]
+    fn test_decrypt() {
+        let mut options = super::parse_args(&args(
+            "decrypt --rotors II,IV,V --rings BUL --start BLA")).unwrap();
+        options.plugboard = "AV BS CG DL FU HZ IN KM OW RX".to_string();
+
+        let mut machine = super::build_machine(&options).unwrap();
+        let output = super::process(&mut machine, &options.command, "EDPUD NRGYS ZRCXN\n").unwrap();
+
+        assert!(output == "AUFKLXABTEILUNG");
+    }
+
+    #[test]
+    fn test_policy() {
+        let options = super::parse_args(&args("decrypt --policy drop --pretty --start QRS")).unwrap();
+        assert!(options.policy == enigma::InputPolicy::Drop && options.pretty);
+        assert!(super::parse_args(&args("encrypt --policy shout")).is_err());
+
+        let mut machine = super::build_machine(&options).unwrap();
+        let output = super::process(&mut machine, &Command::Encrypt, "Feind: 3 Zerstörer.").unwrap();
+        assert!(output.len() == 17 && output.split(' ').all(|x| x.len() == 5));
+    }
+
+    #[test]
+    fn test_output_format() {
+        let options = super::parse_args(&args("encrypt --group 4 --padding random:12")).unwrap();
+        assert!(options.format == OutputFormat { group_size: 4, padding: Padding::Random(12) });
+        assert!(super::parse_args(&args("encrypt --group four")).is_err());
+
+        let mut machine = super::build_machine(&options).unwrap();
+        let cipher = super::process(&mut machine, &Command::Encrypt, "ATTACK AT DAWN NOW").unwrap();
+        assert!(cipher.len() == 19);
+
+        let mut machine = super::build_machine(&options).unwrap();
+        let plain = super::process(&mut machine, &Command::Decrypt, &cipher).unwrap();
+        assert!(plain.starts_with("ATTACKATDAWNNOW") && plain.len() == 16);
+    }
+
+    #[test]
+    fn test_config_file() {
+        let path = std::env::temp_dir().join(format!("enigma-cli-config-{}.txt", std::process::id()));
+        std::fs::write(&path, "rotors = II IV V\nrings = BUL\nplugboard = AV BS CG DL FU HZ IN KM OW RX\nstart = BLA\n").unwrap();
+
+        let options = super::parse_args(&args(&format!("decrypt --config {}", path.display()))).unwrap();
+        let mut machine = super::build_machine(&options).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let output = super::process(&mut machine, &options.command, "EDPUD NRGYS ZRCXN").unwrap();
+        assert!(output == "AUFKLXABTEILUNG");
+
+        let options = super::parse_args(&args("decrypt --config does-not-exist.txt")).unwrap();
+        assert!(super::build_machine(&options).is_err());
+    }
+
+    #[test]
+    fn test_keysheet() {
+        let options = super::parse_args(&args("keysheet --date 1941-07 --seed 5")).unwrap();
+        let sheet = super::generate_keysheet(&options).unwrap();
+        assert!(sheet.days.len() == 31 && sheet.days[0].rotors.len() == 3);
+        assert!(super::generate_keysheet(&super::parse_args(&args("keysheet --seed 5")).unwrap()).is_err());
+
+        let path = std::env::temp_dir().join(format!("enigma-cli-keysheet-{}.json", std::process::id()));
+        std::fs::write(&path, sheet.to_json()).unwrap();
+
+        let options = super::parse_args(&args(&format!(
+            "encrypt --keysheet {} --date 1941-07-12 --start QRS", path.display()))).unwrap();
+        let machine = super::build_machine(&options);
+
+        let options = super::parse_args(&args(&format!(
+            "encrypt --keysheet {} --date 1941-07", path.display()))).unwrap();
+        let no_day = super::build_machine(&options);
+        std::fs::remove_file(&path).unwrap();
+
+        let machine = machine.unwrap();
+        assert!(machine.get_key() == "QRS");
+        assert!(machine.get_rotor_names() == sheet.get_day(12).unwrap().rotors);
+        assert!(no_day.is_err());
+    }
+
+    #[test]
+    fn test_indicator() {
+        let mut options = super::parse_args(&args("decrypt --rotors II,IV,V --rings BUL --indicator single")).unwrap();
+        options.plugboard = "AV BS CG DL FU HZ IN KM OW RX".to_string();
+        assert!(options.indicator == Some(Procedure::Single));
+        let machine = super::build_machine(&options).unwrap();
+
+        let output = super::process_with_indicator(
+            &machine, &options, Procedure::Single, "WXC KCH\nEDPUD NRGYS ZRCXN\n").unwrap();
+        assert!(output == "AUFKLXABTEILUNG");
+
+        options.command = Command::Encrypt;
+        options.indicator = Some(Procedure::Doubled);
+        options.grundstellung = Some("EMV".to_string());
+        let cipher = super::process_with_indicator(&machine, &options, Procedure::Doubled, "GEHEIM").unwrap();
+        assert!(cipher.lines().next().unwrap().len() == 6);
+
+        options.command = Command::Decrypt;
+        assert!(super::process_with_indicator(&machine, &options, Procedure::Doubled, &cipher) == Ok("GEHEIMXXXX".to_string()));
+        assert!(super::parse_args(&args("encrypt --indicator monthly")).is_err());
+    }
+
+    #[test]
+    fn test_radio() {
+        let mut options = super::parse_args(&args("encrypt --rotors II,IV,V --radio --time 1840 --padding none")).unwrap();
+        assert!(options.radio && options.time == Some("1840".to_string()));
+
+        let machine = super::build_machine(&options).unwrap();
+        let plaintext = "ANGRIFFXMORGENXFRUEH".repeat(20);
+        let transmitted = super::process_radio(&machine, &options, &plaintext).unwrap();
+        assert!(transmitted.starts_with("1840 - 2TLE - 1TL - 200 - "));
+
+        options.command = Command::Decrypt;
+        assert!(super::process_radio(&machine, &options, &transmitted) == Ok(plaintext));
+        assert!(super::current_time().len() == 4);
+
+        // Groups longer than a part are cut at the part limit
+        let mut options = super::parse_args(&args("encrypt --radio --time 1840 --group 300 --padding none")).unwrap();
+        let machine = super::build_machine(&options).unwrap();
+        let plaintext = "ANGRIFFXMORGENXFRUEH".repeat(30);
+        let transmitted = super::process_radio(&machine, &options, &plaintext).unwrap();
+        assert!(transmitted.starts_with("1840 - 3TLE - 1TL - 250 - "));
+
+        options.command = Command::Decrypt;
+        assert!(super::process_radio(&machine, &options, &transmitted) == Ok(plaintext));
+
+        // and the filler letters keep every part within it
+        let mut options = super::parse_args(&args("encrypt --radio --indicator single --time 1840 --group 300")).unwrap();
+        let machine = super::build_machine(&options).unwrap();
+        let plaintext = "ANGRIFFXMORGENXFRUEH".repeat(30);
+        let transmitted = super::process_radio(&machine, &options, &plaintext).unwrap();
+        let counts: Vec<&str> = transmitted.lines().filter(|x| x.starts_with("1840")).map(|x| x.split(" - ").nth(3).unwrap()).collect();
+        assert!(counts == vec!["250", "250", "250"]);
+
+        options.command = Command::Decrypt;
+        let received = super::process_radio(&machine, &options, &transmitted).unwrap();
+        assert!(received == plaintext.clone() + &"X".repeat(150));
+    }
+
+    #[test]
+    fn test_attack() {
+        let options = super::parse_args(&args("attack --pool I,II,III --candidates 2 --plugs 0")).unwrap();
+        assert!(options.command == Command::Attack && options.candidates == Some(2));
+        assert!(super::parse_args(&args("attack --plugs many")).is_err());
+
+        let spec = super::attack_spec(&options);
+        assert!(spec.rotor_pool == vec![1, 2, 3] && spec.max_plugs == 0 && spec.reflectors == vec!['B']);
+        assert!(super::attack_spec(&super::parse_args(&args("attack --model M4")).unwrap()).rotor_pool.len() == 8);
+
+        let mut machine = super::build_machine(&super::parse_args(&args("encrypt --rotors III,I,II --start KEY")).unwrap()).unwrap();
+        let cipher = machine.type_phrase("WETTERVORHERSAGEBISKAYAXSTURMAUSWESTXSEEGANGSECHSXSICHTWEITEGERING".repeat(2));
+
+        let report = super::process_attack(&options, &cipher).unwrap();
+        assert!(report.lines().count() == 4);
+        assert!(report.starts_with(" 1. ") && report.contains("III I II B  rings AAA  start KEY  plugboard -"));
+
+        let options = super::parse_args(&args("attack --score trigram --language english")).unwrap();
+        assert!(options.score == "trigram" && options.language == enigma::Language::English);
+        assert!(super::parse_args(&args("attack --language klingon")).is_err());
+
+        let options = super::parse_args(&args("attack --score pentagram")).unwrap();
+        assert!(super::process_attack(&options, "ABCDE").is_err());
+    }
+
+    #[test]
+    fn test_cribs() {
+        let options = super::parse_args(&args("crib --crib WETTERBERICHT --crib keine")).unwrap();
+        assert!(options.command == Command::Crib && options.cribs == vec!["WETTERBERICHT", "keine"]);
+
+        let mut machine = super::build_machine(&super::parse_args(&args("encrypt --rotors II,V,III --start ZAP")).unwrap()).unwrap();
+        let cipher = machine.type_phrase("WETTERBERICHTXKEINEBESONDERENEREIGNISSE".to_string());
+
+        let report = super::process_cribs(&options, &cipher).unwrap();
+        let lines: Vec<&str> = report.lines().collect();
+        assert!(lines[0].starts_with("WETTERBERICHT: ") && lines[0].ends_with(" of 28 positions admissible"));
+        assert!(lines[1].starts_with("KEINE: ") && lines[2].is_empty());
+        assert!(lines[3].starts_with("  1. ") && report.contains("WETTERBERICHT at 0  links 13"));
+
+        assert!(super::process_cribs(&super::parse_args(&args("crib")).unwrap(), &cipher).is_err());
+    }
+
+    #[test]
+    fn test_m4_model_inferred() {
+        let options = super::parse_args(&args("encrypt --rotors Beta,V,VI,VIII --reflector C")).unwrap();
+        assert!(super::build_machine(&options).is_ok());
+
+        let options = super::parse_args(&args("encrypt --model M3 --rotors Beta,V,VI,VIII")).unwrap();
+        assert!(super::build_machine(&options).is_err());
+    }
+}
@@ -0,0 +1,216 @@
+use serde::{Deserialize, Serialize};
+use crate::error::EnigmaError;
+
+// Complete setup of a machine as it would be taken from a key sheet, with the
+// rotors given by name from left to right, e.g. ["II", "IV", "V"]. Empty ring
+// settings or start positions leave the rotors at A.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MachineConfig {
+    pub model: String,
+    pub rotors: Vec<String>,
+    pub reflector: char,
+    #[serde(default)]
+    pub rings: String,
+    #[serde(default)]
+    pub plugboard: String,
+    #[serde(default)]
+    pub start: String
+}
+
+impl Default for MachineConfig {
+    fn default() -> Self {
+        Self {
+            model: "M3".to_string(),
+            rotors: vec!["I".to_string(), "II".to_string(), "III".to_string()],
+            reflector: 'B',
+            rings: String::new(),
+            plugboard: String::new(),
+            start: String::new()
+        }
+    }
+}
+
+// Position of a machine part way through a message, as the letters in the
+// rotor windows and the number of keys pressed since it was set up
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MachineState {
+    pub key: String,
+    pub steps: u64
+}
+
+// Splits the "name = value" lines of the text format, skipping blank lines
+// and comments starting with '#'
+fn parse_lines(text: &str) -> Result<Vec<(String, String)>, EnigmaError> {
+    let mut entries = Vec::new();
+
+    for line in text.lines() {
+        let line = line.split('#').next().unwrap_or("").trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        match line.split_once('=') {
+            Some((name, value)) => entries.push((name.trim().to_ascii_lowercase(), value.trim().to_string())),
+            None => return Err(EnigmaError::MalformedConfig(format!("expected 'name = value' but found '{}'", line)))
+        }
+    }
+
+    Ok(entries)
+}
+
+fn parse_json<'a, T: Deserialize<'a>>(json: &'a str) -> Result<T, EnigmaError> {
+    serde_json::from_str(json).map_err(|e| EnigmaError::MalformedConfig(e.to_string()))
+}
+
+impl MachineConfig {
+    // Human-editable form, one setting per line:
+    //
+    //     model = M3
+    //     rotors = II IV V
+    //     reflector = B
+    //     rings = BUL
+    //     plugboard = AV BS CG DL FU HZ IN KM OW RX
+    //     start = BLA
+    pub fn to_text(&self) -> String {
+        format!(
+            "model = {}\nrotors = {}\nreflector = {}\nrings = {}\nplugboard = {}\nstart = {}\n",
+            self.model, self.rotors.join(" "), self.reflector, self.rings, self.plugboard, self.start
+        )
+    }
+
+    pub fn try_from_text(text: &str) -> Result<Self, EnigmaError> {
+        let mut config = Self::default();
+
+        for (name, value) in parse_lines(text)? {
+            match name.as_str() {
+                "model" => config.model = value.to_ascii_uppercase(),
+                "rotors" => config.rotors = value.split([',', ' ']).filter(|x| !x.is_empty()).map(|x| x.to_string()).collect(),
+                "reflector" => {
+                    let letters: Vec<char> = value.to_ascii_uppercase().chars().collect();
+                    if letters.len() != 1 {
+                        return Err(EnigmaError::MalformedConfig(format!("invalid reflector '{}'", value)));
+                    }
+                    config.reflector = letters[0];
+                },
+                "rings" => config.rings = value,
+                "plugboard" => config.plugboard = value,
+                "start" => config.start = value,
+                _ => return Err(EnigmaError::MalformedConfig(format!("unknown setting '{}'", name)))
+            }
+        }
+
+        Ok(config)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Machine configuration is always serialisable")
+    }
+
+    pub fn try_from_json(json: &str) -> Result<Self, EnigmaError> {
+        parse_json(json)
+    }
+
+    // Reads either format, taking anything starting with a brace to be JSON
+    pub fn try_parse(text: &str) -> Result<Self, EnigmaError> {
+        if text.trim_start().starts_with('{') {
+            Self::try_from_json(text)
+        }
+        else {
+            Self::try_from_text(text)
+        }
+    }
+}
+
+impl MachineState {
+    pub fn to_text(&self) -> String {
+        format!("key = {}\nsteps = {}\n", self.key, self.steps)
+    }
+
+    pub fn try_from_text(text: &str) -> Result<Self, EnigmaError> {
+        let mut key = None;
+        let mut steps = 0;
+
+        for (name, value) in parse_lines(text)? {
+            match name.as_str() {
+                "key" => key = Some(value.to_ascii_uppercase()),
+                "steps" => steps = value.parse()
+                    .map_err(|_| EnigmaError::MalformedConfig(format!("invalid step count '{}'", value)))?,
+                _ => return Err(EnigmaError::MalformedConfig(format!("unknown setting '{}'", name)))
+            }
+        }
+
+        match key {
+            Some(key) => Ok(Self { key, steps }),
+            None => Err(EnigmaError::MalformedConfig("missing setting 'key'".to_string()))
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Machine state is always serialisable")
+    }
+
+    pub fn try_from_json(json: &str) -> Result<Self, EnigmaError> {
+        parse_json(json)
+    }
+
+    pub fn try_parse(text: &str) -> Result<Self, EnigmaError> {
+        if text.trim_start().starts_with('{') {
+            Self::try_from_json(text)
+        }
+        else {
+            Self::try_from_text(text)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MachineConfig, MachineState};
+    use crate::error::EnigmaError;
+
+    fn barbarossa() -> MachineConfig {
+        MachineConfig {
+            model: "M3".to_string(),
+            rotors: vec!["II".to_string(), "IV".to_string(), "V".to_string()],
+            reflector: 'B',
+            rings: "BUL".to_string(),
+            plugboard: "AV BS CG DL FU HZ IN KM OW RX".to_string(),
+            start: "BLA".to_string()
+        }
+    }
+
+    #[test]
+    fn test_config_text() {
+        let config = barbarossa();
+        assert!(MachineConfig::try_from_text(&config.to_text()) == Ok(config.clone()));
+
+        let edited = "# Barbarossa, 7 July 1941\nMODEL = m3\nrotors = II,IV,V\nreflector = b\n\
+                      rings = BUL\nplugboard = AV BS CG DL FU HZ IN KM OW RX\nstart = BLA\n";
+        assert!(MachineConfig::try_from_text(edited) == Ok(config));
+
+        assert!(MachineConfig::try_from_text("rotors: I II III").is_err());
+        assert!(MachineConfig::try_from_text("colour = red")
+            == Err(EnigmaError::MalformedConfig("unknown setting 'colour'".to_string())));
+    }
+
+    #[test]
+    fn test_config_json() {
+        let config = barbarossa();
+        assert!(MachineConfig::try_parse(&config.to_json()) == Ok(config));
+
+        let minimal = r#"{"model": "M4", "rotors": ["Beta", "V", "VI", "VIII"], "reflector": "C"}"#;
+        let config = MachineConfig::try_parse(minimal).unwrap();
+        assert!(config.rotors.len() == 4 && config.start.is_empty());
+        assert!(MachineConfig::try_parse(r#"{"model": "M4"}"#).is_err());
+    }
+
+    #[test]
+    fn test_state() {
+        let state = MachineState { key: "BLA".to_string(), steps: 42 };
+        assert!(MachineState::try_parse(&state.to_text()) == Ok(state.clone()));
+        assert!(MachineState::try_parse(&state.to_json()) == Ok(state));
+        assert!(MachineState::try_parse("steps = 3").is_err());
+        assert!(MachineState::try_parse("key = AAA\nsteps = many").is_err());
+    }
+}
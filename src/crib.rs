@@ -0,0 +1,110 @@
+use crate::bombe::{find_crash, try_letters, Menu};
+use crate::error::EnigmaError;
+use crate::rotor::ALPHA;
+
+// Place at which a crib can lie against the ciphertext, no letter of it
+// falling on the same letter of the ciphertext, with the statistics of the
+// menu it gives the bombe
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Placement {
+    pub crib: String,
+    pub menu: Menu,
+    pub letters: usize,
+    pub loops: usize,
+    pub components: usize,
+    pub central_letter: Option<char>,
+    pub connections: usize
+}
+
+impl Placement {
+    fn new(crib: &str, menu: Menu) -> Self {
+        let central_letter = menu.central_letter();
+
+        Self {
+            crib: crib.to_string(),
+            letters: menu.letters().len(),
+            loops: menu.loops(),
+            components: menu.components(),
+            connections: central_letter.map(|x| menu.connections(x)).unwrap_or(0),
+            central_letter,
+            menu
+        }
+    }
+
+    pub fn get_offset(&self) -> usize {
+        self.menu.offset
+    }
+}
+
+// Number of places a crib of the given length could take in the ciphertext
+pub fn positions(ciphertext_length: usize, crib_length: usize) -> usize {
+    (ciphertext_length + 1).saturating_sub(crib_length)
+}
+
+// Slides each crib along the ciphertext, keeping the places where the
+// reflector does not rule it out. Placements are ranked with those giving
+// the bombe the most loops first, then the longest menus and those falling
+// into the fewest pieces.
+pub fn try_drag<S: AsRef<str>>(ciphertext: &str, cribs: &[S]) -> Result<Vec<Placement>, EnigmaError> {
+    let cipher = try_letters(ciphertext)?;
+    let mut placements = Vec::new();
+
+    for crib in cribs {
+        let letters = try_letters(crib.as_ref())?;
+
+        if letters.is_empty() {
+            return Err(EnigmaError::InvalidCrib("crib has no letters".to_string()));
+        }
+
+        let text: String = letters.iter().map(|&x| ALPHA[x as usize]).collect();
+
+        for offset in (0..positions(cipher.len(), letters.len())).filter(|&x| find_crash(&cipher, &letters, x).is_none()) {
+            placements.push(Placement::new(&text, Menu::from_letters(&cipher, &letters, offset)));
+        }
+    }
+
+    placements.sort_by(|a, b| b.loops.cmp(&a.loops)
+        .then(b.menu.links.len().cmp(&a.menu.links.len()))
+        .then(a.components.cmp(&b.components))
+        .then(a.get_offset().cmp(&b.get_offset())));
+
+    Ok(placements)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::enigma::Enigma;
+
+    #[test]
+    fn test_drag() {
+        let placements = super::try_drag("ABCDE", &["BA", "e"]).unwrap();
+        let offsets = |crib: &str| placements.iter().filter(|x| x.crib == crib).map(|x| x.get_offset()).collect::<Vec<usize>>();
+        assert!(offsets("BA") == vec![0, 2, 3]);
+        assert!(offsets("E") == vec![0, 1, 2, 3]);
+
+        // The only loop is that of BA against AB
+        let placement = &super::try_drag("XABX", &["BA"]).unwrap()[0];
+        assert!(placement.get_offset() == 1 && placement.loops == 1 && placement.letters == 2);
+        assert!(placement.central_letter == Some('A') && placement.connections == 2);
+
+        assert!(super::try_drag("ABCDE", &[" "]).is_err());
+        assert!(super::try_drag("ABCDE", &["A1"]).is_err());
+        assert!(super::try_drag("AB", &["ABC"]).unwrap().is_empty());
+        assert!(super::positions(10, 4) == 7 && super::positions(3, 4) == 0);
+    }
+
+    #[test]
+    fn test_drag_message() {
+        let mut machine = Enigma::new(vec![3, 1, 4], 'B', "M3".to_string());
+        machine.set_key("WXK".to_string());
+        let cipher = machine.type_phrase("FUNKSPRUCHXWETTERBERICHTXKEINEBESONDERENEREIGNISSEXBEDECKT".to_string());
+
+        let cribs = ["WETTERBERICHT", "KEINEBESONDERENEREIGNISSE"];
+        let placements = super::try_drag(&cipher, &cribs).unwrap();
+
+        assert!(placements.iter().any(|x| x.crib == cribs[0] && x.get_offset() == 11));
+        assert!(placements.iter().any(|x| x.crib == cribs[1] && x.get_offset() == 25));
+        assert!(placements.iter().filter(|x| x.crib == cribs[1]).count() < super::positions(58, 25));
+        assert!(placements.windows(2).all(|x| x[0].loops >= x[1].loops));
+    }
+}
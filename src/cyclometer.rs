@@ -0,0 +1,290 @@
+use std::collections::HashMap;
+use std::fmt;
+use crate::bombe::Scrambler;
+use crate::error::EnigmaError;
+use crate::rotor::{try_alpha_index, ALPHA};
+
+// The products AD, BE and CF of the permutations enciphering the first and
+// fourth, second and fifth, and third and sixth letters of the doubled
+// indicators of one day, as far as the intercepted indicators reveal them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Permutations {
+    pub ad: [Option<u8>; 26],
+    pub be: [Option<u8>; 26],
+    pub cf: [Option<u8>; 26]
+}
+
+// Cycle lengths of AD, BE and CF, longest first. The plugboard relabels the
+// letters of the cycles but leaves their lengths alone, so this depends on
+// the wheel order and start position only.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Characteristic {
+    pub ad: Vec<usize>,
+    pub be: Vec<usize>,
+    pub cf: Vec<usize>
+}
+
+// Setting of a machine at the Grundstellung, as listed in the catalogue
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RotorSetting {
+    pub rotors: Vec<String>,
+    pub start: String
+}
+
+// Characteristics of every start position of the wheel orders catalogued,
+// as the Polish card catalogue built with the cyclometer
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Catalogue {
+    entries: HashMap<Characteristic, Vec<RotorSetting>>
+}
+
+fn try_link(permutation: &mut [Option<u8>; 26], from: u8, to: u8) -> Result<(), EnigmaError> {
+    match permutation[from as usize] {
+        Some(x) if x != to => Err(EnigmaError::MalformedIndicator(format!(
+            "{} is sent to both {} and {}", ALPHA[from as usize], ALPHA[x as usize], ALPHA[to as usize]))),
+        _ => {
+            permutation[from as usize] = Some(to);
+            Ok(())
+        }
+    }
+}
+
+// Cycles of a complete permutation, each starting from its first letter
+pub fn cycles(permutation: &[u8; 26]) -> Vec<Vec<char>> {
+    let mut seen = [false; 26];
+    let mut cycles = Vec::new();
+
+    for start in 0..26 {
+        if seen[start] {
+            continue;
+        }
+
+        let mut cycle = Vec::new();
+        let mut x = start;
+        while !seen[x] {
+            seen[x] = true;
+            cycle.push(ALPHA[x]);
+            x = permutation[x] as usize;
+        }
+        cycles.push(cycle);
+    }
+
+    cycles
+}
+
+fn cycle_lengths(permutation: &[u8; 26]) -> Vec<usize> {
+    let mut lengths: Vec<usize> = cycles(permutation).iter().map(|x| x.len()).collect();
+    lengths.sort_by(|a, b| b.cmp(a));
+    lengths
+}
+
+impl Permutations {
+    // Reads the six letter indicators, all enciphered at the same Grundstellung
+    pub fn try_from_indicators<S: AsRef<str>>(indicators: &[S]) -> Result<Self, EnigmaError> {
+        let mut permutations = Self { ad: [None; 26], be: [None; 26], cf: [None; 26] };
+
+        for indicator in indicators {
+            let letters = indicator.as_ref().split_whitespace().collect::<String>().chars()
+                .map(|x| try_alpha_index(x.to_ascii_uppercase()).map(|i| i as u8))
+                .collect::<Result<Vec<u8>, EnigmaError>>()?;
+
+            if letters.len() != 6 {
+                return Err(EnigmaError::MalformedIndicator(format!(
+                    "expected six letters but found '{}'", indicator.as_ref().trim())));
+            }
+
+            try_link(&mut permutations.ad, letters[0], letters[3])?;
+            try_link(&mut permutations.be, letters[1], letters[4])?;
+            try_link(&mut permutations.cf, letters[2], letters[5])?;
+        }
+
+        Ok(permutations)
+    }
+
+    // Letters still missing from each permutation; around eighty messages
+    // were usually enough for all three to be complete
+    pub fn missing(&self) -> usize {
+        [&self.ad, &self.be, &self.cf].iter()
+            .map(|x| x.iter().filter(|y| y.is_none()).count())
+            .sum()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.missing() == 0
+    }
+
+    fn complete(permutation: &[Option<u8>; 26]) -> Option<[u8; 26]> {
+        let mut table = [0u8; 26];
+        for (out, x) in table.iter_mut().zip(permutation) {
+            *out = (*x)?;
+        }
+        Some(table)
+    }
+
+    pub fn try_characteristic(&self) -> Result<Characteristic, EnigmaError> {
+        let tables = [&self.ad, &self.be, &self.cf].iter()
+            .map(|x| Self::complete(x))
+            .collect::<Option<Vec<[u8; 26]>>>()
+            .ok_or_else(|| EnigmaError::MalformedIndicator(format!(
+                "{} letters of the permutations are still unknown", self.missing())))?;
+
+        Ok(Characteristic {
+            ad: cycle_lengths(&tables[0]),
+            be: cycle_lengths(&tables[1]),
+            cf: cycle_lengths(&tables[2])
+        })
+    }
+}
+
+impl fmt::Display for Characteristic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let show = |x: &Vec<usize>| x.iter().map(|y| y.to_string()).collect::<Vec<String>>().join(" ");
+        write!(f, "AD: {} | BE: {} | CF: {}", show(&self.ad), show(&self.be), show(&self.cf))
+    }
+}
+
+// Characteristic of the scrambler at its start position, from the
+// permutations of the first six key presses. Only the right rotor moves,
+// as the middle rotor turning over depends on the ring settings.
+fn scrambler_characteristic(scrambler: &mut Scrambler) -> Characteristic {
+    let mut tables = [[0u8; 26]; 6];
+    for table in tables.iter_mut() {
+        scrambler.step_right();
+        *table = scrambler.table();
+    }
+
+    let product = |first: &[u8; 26], second: &[u8; 26]| {
+        let mut table = [0u8; 26];
+        for (x, out) in table.iter_mut().enumerate() {
+            *out = second[first[x] as usize];
+        }
+        cycle_lengths(&table)
+    };
+
+    Characteristic {
+        ad: product(&tables[0], &tables[3]),
+        be: product(&tables[1], &tables[4]),
+        cf: product(&tables[2], &tables[5])
+    }
+}
+
+impl Catalogue {
+    // Works through every start position of each wheel order with the rings
+    // at A, so that the start positions found are those of the rotor cores.
+    // As with the Polish catalogue, a day whose indicators were sent across
+    // a turnover of the middle rotor is not found under its setting.
+    pub fn try_build(orders: &[Vec<i32>], reflector: char) -> Result<Self, EnigmaError> {
+        let mut catalogue = Self::default();
+
+        for order in orders {
+            let mut scrambler = Scrambler::try_new(order, reflector)?;
+            let rotors: Vec<String> = order.iter()
+                .map(|x| crate::rotor::try_rotors(*x).map(|r| r.get_name()))
+                .collect::<Result<Vec<String>, EnigmaError>>()?;
+
+            for index in 0..26usize.pow(order.len() as u32) {
+                scrambler.set_start(index);
+                let start = scrambler.get_key();
+
+                catalogue.entries.entry(scrambler_characteristic(&mut scrambler))
+                    .or_default()
+                    .push(RotorSetting { rotors: rotors.clone(), start });
+            }
+        }
+
+        Ok(catalogue)
+    }
+
+    pub fn lookup(&self, characteristic: &Characteristic) -> &[RotorSetting] {
+        match self.entries.get(characteristic) {
+            Some(settings) => settings,
+            None => &[]
+        }
+    }
+
+    // Number of distinct characteristics, out of the settings catalogued
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn settings(&self) -> usize {
+        self.entries.values().map(|x| x.len()).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use super::{Catalogue, Permutations};
+    use crate::enigma::Enigma;
+    use crate::indicator::{self, Indicator, Procedure};
+
+    // A day's traffic of doubled indicators, all at the Grundstellung QWE
+    fn indicators(machine: &Enigma, n_messages: usize) -> Vec<String> {
+        let mut rng = StdRng::seed_from_u64(1932);
+        (0..n_messages)
+            .map(|_| Indicator::try_create(machine, Procedure::Doubled, "QWE", &indicator::random_key(&mut rng, 3)).unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_permutations() {
+        let permutations = Permutations::try_from_indicators(&["DMQ VBN", "VON PUY"]).unwrap();
+        assert!(permutations.ad[3] == Some(21) && permutations.be[14] == Some(20));
+        assert!(permutations.missing() == 3 * 24 && !permutations.is_complete());
+        assert!(permutations.try_characteristic().is_err());
+
+        assert!(Permutations::try_from_indicators(&["DMQVBN", "DMQABN"]).is_err());
+        assert!(Permutations::try_from_indicators(&["DMQVB"]).is_err());
+
+        let mut table = [0u8; 26];
+        for (i, x) in table.iter_mut().enumerate() {
+            *x = ((i + 1) % 26) as u8;
+        }
+        assert!(super::cycles(&table).len() == 1 && super::cycle_lengths(&table) == vec![26]);
+    }
+
+    #[test]
+    fn test_characteristic_lookup() {
+        let mut machine = Enigma::new(vec![1, 2, 3], 'B', "M3".to_string());
+        machine.set_plugboard("AB CD EF GH IJ KL");
+
+        let permutations = Permutations::try_from_indicators(&indicators(&machine, 150)).unwrap();
+        assert!(permutations.is_complete());
+
+        let characteristic = permutations.try_characteristic().unwrap();
+        assert!(characteristic.ad.iter().sum::<usize>() == 26);
+
+        // Cycles of each product come in pairs of equal length
+        for lengths in [&characteristic.ad, &characteristic.be, &characteristic.cf] {
+            assert!(lengths.chunks(2).all(|x| x.len() == 2 && x[0] == x[1]));
+        }
+
+        // The plugboard makes no difference to the characteristic
+        machine.set_plugboard("");
+        let unplugged = Permutations::try_from_indicators(&indicators(&machine, 150)).unwrap();
+        assert!(unplugged.try_characteristic() == Ok(characteristic.clone()));
+
+        let catalogue = Catalogue::try_build(&[vec![1, 2, 3]], 'B').unwrap();
+        assert!(catalogue.settings() == 26 * 26 * 26 && catalogue.len() < catalogue.settings());
+
+        let found = catalogue.lookup(&characteristic);
+        assert!(found.iter().any(|x| x.rotors == vec!["I", "II", "III"] && x.start == "QWE"));
+
+        // With the right ring at C a Grundstellung of ADW puts the cores at
+        // ADU, one short of the notch of III, yet no key press turns it over
+        machine.set_ring_settings("AAC");
+        let mut rng = StdRng::seed_from_u64(1933);
+        let near_turnover: Vec<String> = (0..150)
+            .map(|_| Indicator::try_create(&machine, Procedure::Doubled, "ADW", &indicator::random_key(&mut rng, 3)).unwrap().to_string())
+            .collect();
+        let characteristic = Permutations::try_from_indicators(&near_turnover).unwrap().try_characteristic().unwrap();
+        assert!(catalogue.lookup(&characteristic).iter().any(|x| x.start == "ADU"));
+        assert!(characteristic.to_string().starts_with("AD: "));
+    }
+}
@@ -1,23 +1,17 @@
-#[path = "rotor.rs"]
-mod rotor;
-
-#[path = "reflector.rs"]
-mod reflector;
-
-#[path = "plugboard.rs"]
-mod plugboard;
-
-use std::collections::HashMap;
-use rand::Rng;
+use crate::{config, plugboard, reflector, rotor, text};
 use log::{debug};
+use crate::error::EnigmaError;
 
 pub struct Enigma {
     enigma_type: String,
     rotor_ids: Vec::<i32>,
-    rotors: HashMap<String, rotor::Rotor>,
+    rotors: Vec<rotor::Rotor>,
     rotor_labels: Vec::<String>,
     reflector: reflector::Reflector,
-    plugboard: plugboard::Plugboard
+    plugboard: plugboard::Plugboard,
+    input_policy: text::InputPolicy,
+    output_format: text::OutputFormat,
+    steps: u64
 }
 
 impl PartialEq for Enigma {
@@ -35,100 +29,174 @@ impl Clone for Enigma {
             rotors: self.rotors.clone(),
             rotor_labels: self.rotor_labels.clone(),
             reflector: self.reflector.clone(),
-            plugboard: self.plugboard.clone()
+            plugboard: self.plugboard.clone(),
+            input_policy: self.input_policy,
+            output_format: self.output_format,
+            steps: self.steps
         }
     }
 }
 
 impl Enigma {
-    pub fn new(rotor_list: Vec::<i32>, reflector: char, enigma_type: String) -> Self {
-        let mut rotors = HashMap::<String, rotor::Rotor>::new();
-        let mut rotor_labels = Vec::<String>::new();
+    pub fn try_new(rotor_list: Vec::<i32>, reflector: char, enigma_type: String) -> Result<Self, EnigmaError> {
+        let rotor_labels: Vec::<String> = match enigma_type.as_str() {
+            "M3" => vec!["left", "middle", "right"],
+            "M4" => vec!["left", "middle left", "middle right", "right"],
+            _ => return Err(EnigmaError::UnknownModel(enigma_type))
+        }.iter().map(|x| x.to_string()).collect();
+
+        if rotor_list.len() != rotor_labels.len() {
+            return Err(EnigmaError::WrongRotorCount {
+                model: enigma_type,
+                expected: rotor_labels.len(),
+                found: rotor_list.len()
+            });
+        }
 
-        if enigma_type == "M3" {
-            if rotor_list.len() != 3 {
-                panic!("Three rotor types only must be provided for Enigma machine 'M3'");
+        let mut rotors = Vec::<rotor::Rotor>::with_capacity(rotor_list.len());
+
+        for (i, (label, rotor_id)) in rotor_labels.iter().zip(rotor_list.iter()).enumerate() {
+            if rotor_list[..i].contains(rotor_id) {
+                return Err(EnigmaError::DuplicateRotor(*rotor_id));
             }
+            let rotor = rotor::try_rotors(*rotor_id)?;
 
-            rotors.insert("left".to_string(), rotor::Rotors(rotor_list[0]));
-            rotors.insert("middle".to_string(), rotor::Rotors(rotor_list[1]));
-            rotors.insert("right".to_string(), rotor::Rotors(rotor_list[2]));
+            // The Greek wheels only fit the fourth slot of the M4, which cannot take any other rotor
+            if rotor.is_greek() != (enigma_type == "M4" && i == 0) {
+                return Err(EnigmaError::MisplacedRotor { rotor: *rotor_id, position: label.clone() });
+            }
 
-            rotor_labels.push("left".to_string());
-            rotor_labels.push("middle".to_string());
-            rotor_labels.push("right".to_string());
+            rotors.push(rotor);
         }
-        else if enigma_type == "M4" {
-            if rotor_list.len() != 4 {
-                panic!("Three rotor types only must be provided for Enigma machine 'M4'");
-            }
-            rotors.insert("left".to_string(), rotor::Rotors(rotor_list[0]));
-            rotors.insert("middle left".to_string(), rotor::Rotors(rotor_list[1]));
-            rotors.insert("middle right".to_string(), rotor::Rotors(rotor_list[2]));
-            rotors.insert("right".to_string(), rotor::Rotors(rotor_list[3]));
 
-            rotor_labels.push("left".to_string());
-            rotor_labels.push("middle left".to_string());
-            rotor_labels.push("middle right".to_string());
-            rotor_labels.push("right".to_string());
+        let reflector = if enigma_type == "M4" {
+            match reflector::try_thin_reflectors(reflector) {
+                Ok(r) => r,
+                Err(e) => match reflector::try_reflectors(reflector) {
+                    Ok(_) => return Err(EnigmaError::IncompatibleReflector { model: enigma_type, reflector }),
+                    Err(_) => return Err(e)
+                }
+            }
         }
         else {
-            panic!("Unrecognised Enigma type '{}'", enigma_type);
-        }
+            reflector::try_reflectors(reflector)?
+        };
 
-        Self {
+        Ok(Self {
             rotor_ids: rotor_list,
-            rotors: rotors,
-            reflector: reflector::Reflectors(reflector),
-            rotor_labels: rotor_labels,
-            enigma_type: enigma_type,
+            rotors,
+            reflector,
+            rotor_labels,
+            enigma_type,
             plugboard: plugboard::Plugboard::new(),
+            input_policy: text::InputPolicy::default(),
+            output_format: text::OutputFormat::default(),
+            steps: 0
+        })
+    }
+
+    pub fn new(rotor_list: Vec::<i32>, reflector: char, enigma_type: String) -> Self {
+        match Self::try_new(rotor_list, reflector, enigma_type) {
+            Ok(e) => e,
+            Err(e) => panic!("{}", e)
         }
+    }
+
+    pub fn try_from_config(config: &config::MachineConfig) -> Result<Self, EnigmaError> {
+        let rotor_list = config.rotors.iter()
+            .map(|x| rotor::try_rotor_id(x))
+            .collect::<Result<Vec<i32>, EnigmaError>>()?;
 
+        let mut machine = Self::try_new(rotor_list, config.reflector.to_ascii_uppercase(), config.model.to_ascii_uppercase())?;
+
+        if !config.rings.trim().is_empty() {
+            machine.try_set_ring_settings(&config.rings)?;
+        }
+
+        machine.try_set_plugboard(&config.plugboard)?;
+
+        if !config.start.trim().is_empty() {
+            machine.try_set_key(config.start.trim().to_string())?;
+        }
+
+        Ok(machine)
     }
 
-    fn _get_rotor_at_position(&self, label: &String) -> &rotor::Rotor {
-        match self.rotors.get(label) {
-            Some(r) => return r,
-            None => panic!("Failed to retrieve rotor at position '{}' from rotor list", label)
+    pub fn from_config(config: &config::MachineConfig) -> Self {
+        match Self::try_from_config(config) {
+            Ok(e) => e,
+            Err(e) => panic!("{}", e)
         }
     }
 
-    fn _get_rotor_at_position_mut(&mut self, label: &String) -> &mut rotor::Rotor {
-        match self.rotors.get_mut(label) {
-            Some(r) => return r,
-            None => panic!("Failed to retrieve mutable reference to rotor at position '{}' from rotor list", label)
+    // Configuration reproducing the machine as it stands, taking the current
+    // window letters as the start position
+    pub fn to_config(&self) -> config::MachineConfig {
+        config::MachineConfig {
+            model: self.get_type(),
+            rotors: self.get_rotor_names(),
+            reflector: self.get_reflector_name(),
+            rings: self.get_ring_settings(),
+            plugboard: self.plugboard.to_string(),
+            start: self.get_key()
         }
     }
 
-    fn rotor_index(&self, label: &String) -> usize {
-        match self.rotor_labels.iter().enumerate().find(|&x| x.1 == label) {
-            Some(x) => x.0,
-            None => panic!("Could not find Rotor, '{}' in rotor list", label)
+    pub fn snapshot(&self) -> config::MachineState {
+        config::MachineState {
+            key: self.get_key(),
+            steps: self.steps
         }
     }
 
-    fn _move_rotor(&mut self, rotor: &String, amount: i32) {
-        for i in 0..amount {
-            debug!("[{}] Rotating rotor {} by {}", i, rotor, amount);
+    pub fn try_restore(&mut self, state: &config::MachineState) -> Result<(), EnigmaError> {
+        self.try_set_key(state.key.clone())?;
+        self.steps = state.steps;
+        Ok(())
+    }
 
-            match self.rotors.get_mut(rotor) {
-                Some(r) => r.rotate(None),
-                None => panic!("Failed to retrieve rotor at position '{}' from rotor list", rotor)
-            }
+    pub fn restore(&mut self, state: &config::MachineState) {
+        if let Err(e) = self.try_restore(state) {
+            panic!("{}", e);
         }
     }
 
-    fn _set_rotor(&mut self, rotor: &String, letter: char) {
-        debug!("Setting rotor {} to {}", rotor, letter);
-        let mut face = self._get_rotor_at_position(rotor).get_face_letter();
+    // Number of keys pressed since the machine was built or last restored
+    pub fn get_steps(&self) -> u64 {
+        self.steps
+    }
 
-        while face  != letter {
-            self._move_rotor(rotor, 1);
-            face = self._get_rotor_at_position(rotor).get_face_letter();
+    fn _rotor_index(&self, label: &String) -> usize {
+        match self.rotor_labels.iter().position(|x| x == label) {
+            Some(i) => i,
+            None => panic!("Failed to retrieve rotor at position '{}' from rotor list", label)
         }
     }
 
+    fn _get_rotor_at_position(&self, label: &String) -> &rotor::Rotor {
+        &self.rotors[self._rotor_index(label)]
+    }
+
+    pub fn get_key(&self) -> String {
+        self.rotors.iter().map(|x| x.get_face_letter()).collect()
+    }
+
+    pub fn get_type(&self) -> String {
+        self.enigma_type.clone()
+    }
+
+    pub fn get_rotor_ids(&self) -> Vec<i32> {
+        self.rotor_ids.clone()
+    }
+
+    pub fn get_rotor_names(&self) -> Vec<String> {
+        self.rotors.iter().map(|x| x.get_name()).collect()
+    }
+
+    pub fn get_reflector_name(&self) -> char {
+        self.reflector.get_name()
+    }
+
     pub fn rotor_conv(&self, rotor: &String, letter: char) -> char {
         self._get_rotor_at_position(rotor).convert(letter)
     }
@@ -149,168 +217,215 @@ impl Enigma {
         self.plugboard.convert_inv(letter)
     }
 
-    pub fn inter_rotor_conv(&self, rotor_1: &String, rotor_2: &String, letter: char) -> char {
-        let terminal = rotor::alpha_index(letter);
-        let zero_point_1 = rotor::alpha_index(self._get_rotor_at_position(rotor_1).get_face_letter());
-        let zero_point_2 = rotor::alpha_index(self._get_rotor_at_position(rotor_2).get_face_letter());
-        let interval = zero_point_2 as i32 - zero_point_1 as i32;
+    // Sets the ring of each rotor, from left to right, to the given offset from A
+    pub fn ringstellung(&mut self, rsg_vec: Vec<i32>) {
+        for (rotor, amount) in self.rotors.iter_mut().zip(rsg_vec) {
+            rotor.set_ring_offset(amount.rem_euclid(26) as u8);
+        }
+    }
+
+    pub fn get_ring_settings(&self) -> String {
+        self.rotors.iter().map(|x| x.get_ring_setting()).collect()
+    }
 
-        let n;
+    // Ring settings as letters, "BUL", or numbers, "02 21 12", for each rotor from left to right
+    pub fn try_set_ring_settings(&mut self, settings: &str) -> Result<(), EnigmaError> {
+        let rings = rotor::try_parse_ring_settings(settings)?;
 
-        if zero_point_2 > zero_point_1 {
-            let i: Vec<i32> = (0..26).collect();
-            n = i[(terminal + interval as usize) % i.len()];
+        if rings.len() != self.rotors.len() {
+            return Err(EnigmaError::RingSettingCountMismatch {
+                expected: self.rotors.len(),
+                found: rings.len()
+            });
         }
-        else {
-            let i: Vec<i32> = (0..26).collect();
-            let index: i32 = 26 + terminal as i32 + interval as i32;
-            n = i[(index as usize) % i.len()];
+
+        for (rotor, ring) in self.rotors.iter_mut().zip(rings) {
+            rotor.set_ring_offset(ring);
         }
-        println!("N: {}, {}", n, rotor::ALPHA[n as usize]);
 
-        rotor::ALPHA[n as usize]
+        Ok(())
     }
 
-    fn ringstellung_rotor_(&mut self, rotor: &String, amount: i32) {
-        for _i in 0..amount {
-            self._get_rotor_at_position_mut(rotor).rotate_inner_ring();
+    pub fn set_ring_settings(&mut self, settings: &str) {
+        if let Err(e) = self.try_set_ring_settings(settings) {
+            panic!("{}", e);
         }
     }
 
-    pub fn ringstellung(&mut self, rsg_vec: Vec<i32>) {
-        let rotor_labels = self.rotor_labels.clone();
-        for i in 0..rsg_vec.len() {
-            self.ringstellung_rotor_(&rotor_labels[i], rsg_vec[i]);
-        }
+    // Enciphers a single letter given as an alphabet index (A = 0). This is
+    // the hot path used by the cryptanalysis routines, so it works purely on
+    // the wiring tables and does no validation or allocation.
+    #[inline]
+    pub fn type_index(&mut self, index: u8) -> u8 {
+        self.step();
+        self.plugboard.swap(self.scramble(self.plugboard.swap(index)))
     }
 
-    pub fn type_letter(&mut self, letter: char) -> char {
-        let upper_l = letter.to_ascii_uppercase();
+    #[inline]
+    pub(crate) fn step(&mut self) {
+        rotor::step_rotors(&mut self.rotors);
+        self.steps += 1;
+    }
+
+    // Path through the rotors and reflector alone, without the plugboard
+    #[inline]
+    fn scramble(&self, index: u8) -> u8 {
+        let mut cipher = index;
 
-        let mut cipher_af = self.plugboard.convert(upper_l);
-        debug!("Plugboard: {} -> {}", upper_l, cipher_af);
+        for rotor in self.rotors.iter().rev() {
+            cipher = rotor.forward(cipher);
+        }
 
-        let rotor_labels = self.rotor_labels.clone();
+        cipher = self.reflector.reflect(cipher);
 
-        let (_, offset_1) = rotor_labels.split_at(1);
-        let (offset_2, _) = rotor_labels.split_at(self.rotor_labels.len()-1);
+        for rotor in self.rotors.iter() {
+            cipher = rotor.backward(cipher);
+        }
 
-        let mut notch_dict = HashMap::<String, Vec<char>>::new();
-        let mut face_letters = HashMap::<String, char>::new();
+        cipher
+    }
 
-        for rotor_label in self.rotor_labels.clone() {
-            notch_dict.insert(rotor_label.clone(), self._get_rotor_at_position(&rotor_label).get_notches());
-            face_letters.insert(rotor_label.clone(), self._get_rotor_at_position(&rotor_label).get_face_letter());
+    // Substitution made by the rotors and reflector at their current
+    // positions, which the plugboard is wrapped around
+    pub(crate) fn scrambler_table(&self) -> [u8; 26] {
+        let mut table = [0u8; 26];
+        for (x, out) in table.iter_mut().enumerate() {
+            *out = self.scramble(x as u8);
         }
+        table
+    }
 
-        for rotors in offset_1.iter().rev().zip(offset_2.iter().rev()) {
-            let (rotor_1, rotor_2) = rotors;
-            let notches;
+    pub fn try_type_letter(&mut self, letter: char) -> Result<char, EnigmaError> {
+        let upper_l = letter.to_ascii_uppercase();
+        let index = rotor::try_alpha_index(upper_l)? as u8;
 
-            match notch_dict.get(&rotor_1.clone()) {
-                Some(n) => notches = n,
-                None => panic!("Could not retrieve notch locations for rotor at position '{}'", rotor_1)
-            }
+        let cipher = rotor::ALPHA[self.type_index(index) as usize];
+        debug!("[{}] {} -> {}", self.get_key(), upper_l, cipher);
 
-            for notch in notches {
-                if face_letters[&rotor_1.clone()] == *notch {
-                    self._move_rotor(&rotor_2.clone(), 1);
-                }
-            }
-        }
+        Ok(cipher)
+    }
 
-        self._move_rotor(&self.rotor_labels.clone()[&self.rotor_labels.len()-1], 1);
+    pub fn type_letter(&mut self, letter: char) -> char {
+        match self.try_type_letter(letter) {
+            Ok(c) => c,
+            Err(e) => panic!("{}", e)
+        }
+    }
 
-        let mut cipher = cipher_af;
+    pub fn get_input_policy(&self) -> text::InputPolicy {
+        self.input_policy
+    }
 
-        for rotor in self.rotor_labels.iter().rev() {
-            cipher = cipher_af.clone();
-            cipher_af = self.rotor_conv(rotor, cipher);
-            debug!("Rotor {} conversion: {} -> {}", &rotor, cipher, cipher_af);
-            cipher = cipher_af.clone();
-            let adj_rotor_index = self.rotor_index(&rotor) as i32 -1;
-            if adj_rotor_index < 0 {
-                break;
-            }
+    pub fn set_input_policy(&mut self, policy: text::InputPolicy) {
+        self.input_policy = policy;
+    }
 
-            let adjacent_rotor = &self.rotor_labels[adj_rotor_index as usize];
+    pub fn get_output_format(&self) -> text::OutputFormat {
+        self.output_format
+    }
 
-            cipher_af = self.inter_rotor_conv(&rotor, adjacent_rotor, cipher);
-            debug!("Inter-Rotor {} to {} conversion: {} -> {}", &rotor, adjacent_rotor, cipher, cipher_af);
-            cipher = cipher_af.clone();
-        }
+    pub fn set_output_format(&mut self, format: text::OutputFormat) {
+        self.output_format = format;
+    }
 
-        cipher_af = self.reflector_conv(cipher);
-        debug!("Reflector conversion: {} -> {}", cipher, cipher_af);
-        cipher = cipher_af.clone();
+    fn _type_prepared(&mut self, prepared: &str) -> Result<String, EnigmaError> {
+        let mut out_str = String::with_capacity(prepared.len());
 
-        for rotor in self.rotor_labels.clone() {
-            cipher_af = self.rotor_conv_inv(&rotor, cipher);
-            debug!("Rotor {} conversion: {} -> {}", &rotor, cipher, cipher_af);
-            cipher = cipher_af.clone();
-            let adj_rotor_index = self.rotor_index(&rotor) as i32 + 1;
-            if adj_rotor_index >= self.rotor_labels.len() as i32 {
-                break;
+        for letter in prepared.chars() {
+            // Only reached for characters let through by the input policy
+            if !letter.is_ascii_uppercase() {
+                out_str.push(letter);
+                continue;
             }
-            let adjacent_rotor = &self.rotor_labels[adj_rotor_index as usize];
-            cipher_af = self.inter_rotor_conv(&rotor, adjacent_rotor, cipher);
-            debug!("Inter-Rotor {} to {} conversion: {} -> {}", &rotor, adjacent_rotor, cipher, cipher_af);
-            cipher = cipher_af.clone();
+
+            out_str.push(self.try_type_letter(letter)?);
         }
 
-        cipher_af = self.plugboard_conv_inv(cipher);
-        debug!("Plugboard: {} -> {}", cipher, cipher_af);
-        debug!("--------------------");
-        cipher_af
+        Ok(out_str)
     }
 
-    pub fn type_phrase(&mut self, phrase: String) -> String {
-        let mut rng = rand::thread_rng();
-        let mut temp = phrase.clone();
-        temp.retain(|x| !x.is_whitespace());
+    // Enciphers a message, padding and grouping the result as given by the output format
+    pub fn try_type_phrase(&mut self, phrase: String) -> Result<String, EnigmaError> {
+        let mut temp = text::try_prepare(&phrase, self.input_policy)?;
+        temp += &self.output_format.try_padding(&temp, &self.get_key())?;
 
-        let remainder = if temp.len() % 5 != 0 {5 - temp.len() % 5} else {0};
+        let out_str = self._type_prepared(&temp)?;
+        Ok(self.output_format.group(&out_str))
+    }
 
-        for _i in 0..remainder {
-            temp.push(rotor::ALPHA[rng.gen_range(0..25) as usize]);
+    pub fn type_phrase(&mut self, phrase: String) -> String {
+        match self.try_type_phrase(phrase) {
+            Ok(s) => s,
+            Err(e) => panic!("{}", e)
         }
+    }
+
+    // Deciphers a received message, ignoring the group spacing and without any padding
+    pub fn try_decode_phrase(&mut self, phrase: &str) -> Result<String, EnigmaError> {
+        let temp = text::try_prepare(&text::strip_groups(phrase), self.input_policy)?;
+        self._type_prepared(&temp)
+    }
 
-        let mut out_str: String = "".to_string();
+    pub fn decode_phrase(&mut self, phrase: &str) -> String {
+        match self.try_decode_phrase(phrase) {
+            Ok(s) => s,
+            Err(e) => panic!("{}", e)
+        }
+    }
 
-        for i in 0..temp.len() {
-            let letter;
+    pub fn try_set_key(&mut self, user_key: String) -> Result<(), EnigmaError> {
+        let upper_k = user_key.to_ascii_uppercase();
+        let n_letters = upper_k.chars().count();
 
-            match temp.chars().nth(i) {
-                Some(l) => letter = l,
-                None => panic!(
-                    "Could not retrieve letter at position '{}' of modified phrase string '{}'", i, temp)
-            }
+        if self.rotor_labels.len() != n_letters {
+            return Err(EnigmaError::KeyLengthMismatch {
+                expected: self.rotor_labels.len(),
+                found: n_letters
+            });
+        }
 
-            out_str += &self.type_letter(letter).to_string();
+        for letter in upper_k.chars() {
+            rotor::try_alpha_index(letter)?;
+        }
 
-            if i + 1 % 5 == 0 {
-                out_str += " ";
-            }
+        for (rotor, letter) in self.rotors.iter_mut().zip(upper_k.chars()) {
+            rotor.try_set_face_letter(letter)?;
         }
-        out_str
+
+        Ok(())
     }
 
     pub fn set_key(&mut self, user_key: String) {
-        let labels = self.rotor_labels.clone();
-        if labels.len() != user_key.len() {
-            panic!("Key length must match no. of rotors.");
+        if let Err(e) = self.try_set_key(user_key) {
+            panic!("{}", e);
         }
+    }
 
-        let upper_k = user_key.to_ascii_uppercase();
+    pub fn try_rewire_plugboard(&mut self, letter_1: char, letter_2: char) -> Result<(), EnigmaError> {
+        self.plugboard.try_connect(letter_1, letter_2)
+    }
 
-        for (rotor_dict_key, letter) in self.rotor_labels.clone().iter().zip(upper_k.chars()) {
-            let key = rotor_dict_key.clone();
-            self._set_rotor(&key.clone(), letter.clone());
+    pub fn rewire_plugboard(&mut self, letter_1: char, letter_2: char) {
+        self.plugboard.swap_letter_wiring(letter_1, letter_2);
+    }
+
+    pub fn try_set_plugboard(&mut self, pairs: &str) -> Result<(), EnigmaError> {
+        self.plugboard = plugboard::Plugboard::try_from_pairs(pairs)?;
+        Ok(())
+    }
+
+    pub fn set_plugboard(&mut self, pairs: &str) {
+        if let Err(e) = self.try_set_plugboard(pairs) {
+            panic!("{}", e);
         }
     }
 
-    pub fn rewire_plugboard(mut self, letter_1: char, letter_2: char) {
-        self.plugboard.swap_letter_wiring(letter_1, letter_2);
+    pub fn try_unplug(&mut self, letter: char) -> Result<Option<char>, EnigmaError> {
+        self.plugboard.try_disconnect(letter)
+    }
+
+    pub fn plugboard_pairs(&self) -> Vec<(char, char)> {
+        self.plugboard.pairs()
     }
 }
 
@@ -322,15 +437,124 @@ mod tests {
     }
     #[test]
     fn test_type_letter() {
-        let rotor_list = vec![1, 2, 3, 4];
+        let rotor_list = vec![crate::rotor::BETA, 1, 2, 3];
         let enigma_type = "M4".to_string();
         let reflector = 'B';
         let letter = 'K';
-        let before = letter.clone();
+        let before = letter;
         let mut machine = super::Enigma::new(rotor_list.clone(), reflector, enigma_type.clone());
         assert!(machine.type_letter(letter) != before);
     }
 
+    #[test]
+    fn test_invalid_machine() {
+        use crate::error::EnigmaError;
+
+        assert!(super::Enigma::try_new(vec![1, 2, 3], 'B', "M5".to_string()).err()
+            == Some(EnigmaError::UnknownModel("M5".to_string())));
+        assert!(super::Enigma::try_new(vec![1, 2], 'B', "M3".to_string()).err()
+            == Some(EnigmaError::WrongRotorCount { model: "M3".to_string(), expected: 3, found: 2 }));
+        assert!(super::Enigma::try_new(vec![1, 2, 1], 'B', "M3".to_string()).err()
+            == Some(EnigmaError::DuplicateRotor(1)));
+        assert!(super::Enigma::try_new(vec![1, 2, 11], 'B', "M3".to_string()).err()
+            == Some(EnigmaError::UnknownRotor(11)));
+        assert!(super::Enigma::try_new(vec![1, 2, 3], 'X', "M3".to_string()).err()
+            == Some(EnigmaError::UnknownReflector('X')));
+    }
+
+    #[test]
+    fn test_invalid_m4() {
+        use crate::error::EnigmaError;
+        use crate::rotor::{BETA, GAMMA};
+
+        assert!(super::Enigma::try_new(vec![1, 2, 3, 4], 'B', "M4".to_string()).err()
+            == Some(EnigmaError::MisplacedRotor { rotor: 1, position: "left".to_string() }));
+        assert!(super::Enigma::try_new(vec![BETA, GAMMA, 2, 3], 'B', "M4".to_string()).err()
+            == Some(EnigmaError::MisplacedRotor { rotor: GAMMA, position: "middle left".to_string() }));
+        assert!(super::Enigma::try_new(vec![1, BETA, 3], 'B', "M3".to_string()).err()
+            == Some(EnigmaError::MisplacedRotor { rotor: BETA, position: "middle".to_string() }));
+        assert!(super::Enigma::try_new(vec![BETA, 1, 2, 3], 'A', "M4".to_string()).err()
+            == Some(EnigmaError::IncompatibleReflector { model: "M4".to_string(), reflector: 'A' }));
+        assert!(super::Enigma::try_new(vec![GAMMA, 1, 2, 3], 'X', "M4".to_string()).err()
+            == Some(EnigmaError::UnknownReflector('X')));
+    }
+
+    #[test]
+    fn test_m4_compatible_with_m3() {
+        use crate::rotor::{BETA, GAMMA};
+
+        let message = "DASOBERKOMMANDODERWEHRMACHTGIBTBEKANNT";
+
+        for (greek, reflector) in [(BETA, 'B'), (GAMMA, 'C')] {
+            let mut m3 = super::Enigma::new(vec![2, 4, 5], reflector, "M3".to_string());
+            let mut m4 = super::Enigma::new(vec![greek, 2, 4, 5], reflector, "M4".to_string());
+            m3.set_plugboard("AV BS CG DL FU HZ IN KM OW RX");
+            m4.set_plugboard("AV BS CG DL FU HZ IN KM OW RX");
+            m3.set_key("BLA".to_string());
+            m4.set_key("ABLA".to_string());
+
+            let out_m3: String = message.chars().map(|x| m3.type_letter(x)).collect();
+            let out_m4: String = message.chars().map(|x| m4.type_letter(x)).collect();
+            assert!(out_m3 == out_m4);
+        }
+    }
+
+    #[test]
+    fn test_invalid_input() {
+        use crate::error::EnigmaError;
+
+        let mut machine = super::Enigma::new(vec![1, 2, 3], 'B', "M3".to_string());
+        assert!(machine.try_set_key("ABCD".to_string())
+            == Err(EnigmaError::KeyLengthMismatch { expected: 3, found: 4 }));
+        assert!(machine.try_set_key("A1C".to_string()) == Err(EnigmaError::InvalidLetter('1')));
+        assert!(machine.try_type_letter('!') == Err(EnigmaError::InvalidLetter('!')));
+        assert!(machine.try_type_phrase("HELLO 42".to_string()) == Err(EnigmaError::InvalidLetter('4')));
+    }
+
+    #[test]
+    fn test_plugboard_encoding() {
+        let rotor_list = vec![1, 2, 3];
+        let message = "WHATWOULDBRIANBOITRUDO";
+
+        let mut plain = super::Enigma::new(rotor_list.clone(), 'B', "M3".to_string());
+        let mut machine = super::Enigma::new(rotor_list.clone(), 'B', "M3".to_string());
+        machine.set_plugboard("AV BS CG DL FU HZ IN KM OW RX");
+        machine.rewire_plugboard('E', 'T');
+        assert!(machine.plugboard_pairs().len() == 11);
+        assert!(machine.try_rewire_plugboard('E', 'Q').is_err());
+
+        let result = machine.type_phrase(message.to_string());
+        assert!(result != plain.type_phrase(message.to_string()));
+
+        machine = super::Enigma::new(rotor_list.clone(), 'B', "M3".to_string());
+        machine.set_plugboard("AV BS CG DL FU HZ IN KM OW RX ET");
+        let out = machine.decode_phrase(&result);
+
+        assert!(out == message.to_string() + "XXX");
+    }
+
+    #[test]
+    fn test_double_step() {
+        let mut machine = super::Enigma::new(vec![1, 2, 3], 'B', "M3".to_string());
+        machine.set_key("ADU".to_string());
+
+        for expected in ["ADV", "AEW", "BFX", "BFY"] {
+            machine.type_letter('A');
+            assert!(machine.get_key() == expected);
+        }
+    }
+
+    #[test]
+    fn test_m4_fourth_rotor_static() {
+        let mut machine = super::Enigma::new(vec![crate::rotor::BETA, 1, 2, 3], 'B', "M4".to_string());
+        machine.set_key("ZQDT".to_string());
+
+        for expected in ["ZQDU", "ZQDV", "ZQEW", "ZRFX"] {
+            machine.type_letter('A');
+            assert!(machine.get_key() == expected);
+        }
+    }
+
     #[test]
     fn test_m3_encoding() {
         let rotor_list = vec![4, 3, 2];
@@ -346,14 +570,91 @@ mod tests {
 
         machine = super::Enigma::new(rotor_list.clone(), reflector, enigma_type.clone());
         machine.set_key(key.clone());
-        let out = machine.type_phrase(result);
+        let out = machine.decode_phrase(&result);
+
+        assert!(result.split(' ').all(|x| x.len() == 5));
+        assert!(out == message.to_string() + "X");
+    }
+
+    #[test]
+    fn test_input_policy() {
+        use crate::text::{prettify, InputPolicy};
+
+        let mut machine = super::Enigma::new(vec![1, 2, 3], 'B', "M3".to_string());
+        machine.set_input_policy(InputPolicy::PassThrough);
+        let result = machine.type_phrase("HELLO, WORLD!".to_string());
+        assert!(&result[5..6] == "," && &result[12..13] == "!");
+
+        machine = super::Enigma::new(vec![1, 2, 3], 'B', "M3".to_string());
+        machine.set_input_policy(InputPolicy::Transliterate);
+        let result = machine.type_phrase("Nachts 2 Schiffe.".to_string());
+
+        machine = super::Enigma::new(vec![1, 2, 3], 'B', "M3".to_string());
+        let out = machine.decode_phrase(&result);
+        assert!(out.starts_with("NAQTSZWOSQIFFEX"));
+        assert!(prettify(&out[..15]) == "NACHTSZWOSCHIFFE.");
+    }
 
-        assert!(&out[..out.len()-1] == message);
+    #[test]
+    fn test_ring_settings_order() {
+        use crate::error::EnigmaError;
+
+        let message = "AUFKLARUNGOSTWAERTS";
+        let mut rings_first = super::Enigma::new(vec![2, 4, 5], 'B', "M3".to_string());
+        rings_first.set_ring_settings("02 21 12");
+        rings_first.set_key("BLA".to_string());
+
+        let mut key_first = super::Enigma::new(vec![2, 4, 5], 'B', "M3".to_string());
+        key_first.set_key("BLA".to_string());
+        key_first.set_ring_settings("BUL");
+
+        assert!(key_first.get_ring_settings() == "BUL");
+        assert!(rings_first.type_phrase(message.to_string()) == key_first.type_phrase(message.to_string()));
+        assert!(key_first.try_set_ring_settings("AB")
+            == Err(EnigmaError::RingSettingCountMismatch { expected: 3, found: 2 }));
+    }
+
+    #[test]
+    fn test_config_and_snapshot() {
+        use crate::config::{MachineConfig, MachineState};
+
+        let config = MachineConfig::try_from_text(
+            "rotors = II IV V\nrings = 02 21 12\nplugboard = AV BS CG DL FU HZ IN KM OW RX\nstart = BLA").unwrap();
+        let mut machine = super::Enigma::from_config(&config);
+        assert!(machine.to_config().rings == "BUL");
+
+        machine.type_phrase("EDPUDNRGYSZRCXNUYTPOMRMBO".to_string());
+        let state = machine.snapshot();
+        assert!(state == MachineState { key: "BLZ".to_string(), steps: 25 });
+
+        let rest = machine.type_phrase("FKTCG".to_string());
+        let mut resumed = super::Enigma::from_config(&machine.to_config());
+        resumed.restore(&state);
+        assert!(resumed.get_steps() == 25);
+        assert!(resumed.type_phrase("FKTCG".to_string()) == rest);
+        assert!(resumed.try_restore(&MachineState { key: "AB".to_string(), steps: 0 }).is_err());
+
+        let mut config = super::Enigma::new(vec![1, 2, 3], 'B', "M3".to_string()).to_config();
+        config.rotors[2] = "IX".to_string();
+        assert!(super::Enigma::try_from_config(&config).is_err());
+    }
+
+    #[test]
+    fn test_type_index() {
+        let mut by_letter = super::Enigma::new(vec![2, 4, 5], 'B', "M3".to_string());
+        by_letter.set_plugboard("AV BS CG DL FU HZ IN KM OW RX");
+        let mut by_index = by_letter.clone();
+
+        for letter in "THEQUICKBROWNFOXJUMPSOVERTHELAZYDOG".chars() {
+            let index = crate::rotor::alpha_index(letter) as u8;
+            assert!(crate::rotor::ALPHA[by_index.type_index(index) as usize] == by_letter.type_letter(letter));
+        }
+        assert!(by_index.get_key() == by_letter.get_key());
     }
 
     #[test]
     fn test_m4_encoding() {
-        let rotor_list = vec![4, 3, 2, 1];
+        let rotor_list = vec![crate::rotor::GAMMA, 4, 3, 2];
         let enigma_type = "M4".to_string();
         let reflector = 'B';
         let key = "MOAN".to_string();
@@ -366,14 +667,15 @@ mod tests {
 
         machine = super::Enigma::new(rotor_list.clone(), reflector, enigma_type.clone());
         machine.set_key(key.clone());
-        let out = machine.type_phrase(result);
+        let out = machine.decode_phrase(&result);
 
         assert!(out == message);
     }
 
     #[test]
     fn test_ringstellung() {
-        let rotor_list = vec![1, 2, 3, 4];
+        let rotor_list = vec![crate::rotor::BETA, 1, 2, 3];
+        let ring_settings = vec![1, 2, 3, 4];
         let enigma_type = "M4".to_string();
         let reflector = 'B';
         let key = "TEST".to_string();
@@ -381,13 +683,16 @@ mod tests {
         let mut machine = super::Enigma::new(rotor_list.clone(), reflector, enigma_type.clone());
 
         machine.set_key(key.clone());
-        machine.ringstellung(rotor_list.clone());
-        let result = machine.type_phrase(message.clone()).replace(" ", "");
+        machine.ringstellung(ring_settings.clone());
+        machine.set_output_format(crate::text::OutputFormat::ungrouped());
+        let result = machine.type_phrase(message.clone());
+        assert!(result.len() == message.len());
+
         machine = super::Enigma::new(rotor_list.clone(), reflector, enigma_type.clone());
         machine.set_key(key.clone());
-        machine.ringstellung(rotor_list.clone());
-        let orig = machine.type_phrase(result.clone()).replace(" ", "");
+        machine.ringstellung(ring_settings.clone());
+        let orig = machine.decode_phrase(&result);
 
-        assert!(&orig[..orig.len()-4] == message);
+        assert!(orig == message);
     }
 }
\ No newline at end of file
@@ -0,0 +1,89 @@
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EnigmaError {
+    InvalidLetter(char),
+    UnknownRotor(i32),
+    UnknownRotorName(String),
+    UnknownReflector(char),
+    UnknownModel(String),
+    WrongRotorCount { model: String, expected: usize, found: usize },
+    DuplicateRotor(i32),
+    MisplacedRotor { rotor: i32, position: String },
+    IncompatibleReflector { model: String, reflector: char },
+    InvalidPlugboardPair(char, char),
+    MalformedPlugboardPair(String),
+    PlugboardFull,
+    KeyLengthMismatch { expected: usize, found: usize },
+    InvalidRingSetting(String),
+    RingSettingCountMismatch { expected: usize, found: usize },
+    MalformedConfig(String),
+    InvalidKeySheet(String),
+    NoDailyKey { year: u32, month: u32, day: u32 },
+    MalformedIndicator(String),
+    MalformedMessage(String),
+    InvalidBigramTable(String),
+    InvalidNgramCounts(String),
+    InvalidCrib(String),
+    CribCrash { position: usize, letter: char },
+    PlugboardContradiction { letter: char }
+}
+
+impl fmt::Display for EnigmaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EnigmaError::InvalidLetter(c) =>
+                write!(f, "Invalid character '{}', expected a letter A-Z", c),
+            EnigmaError::UnknownRotor(id) =>
+                write!(f, "No rotor for id {}", id),
+            EnigmaError::UnknownRotorName(name) =>
+                write!(f, "No rotor named '{}'", name),
+            EnigmaError::UnknownReflector(r) =>
+                write!(f, "Unrecognised reflector type '{}'", r),
+            EnigmaError::UnknownModel(m) =>
+                write!(f, "Unrecognised Enigma type '{}'", m),
+            EnigmaError::WrongRotorCount { model, expected, found } =>
+                write!(f, "Enigma machine '{}' requires {} rotors, but {} were provided", model, expected, found),
+            EnigmaError::DuplicateRotor(id) =>
+                write!(f, "Rotor {} can only be used once", id),
+            EnigmaError::MisplacedRotor { rotor, position } =>
+                write!(f, "Rotor {} cannot be fitted in the '{}' position", rotor, position),
+            EnigmaError::IncompatibleReflector { model, reflector } =>
+                write!(f, "Reflector '{}' cannot be used with Enigma machine '{}'", reflector, model),
+            EnigmaError::InvalidPlugboardPair(a, b) =>
+                write!(f, "Cannot connect plugboard pair '{}{}'", a, b),
+            EnigmaError::MalformedPlugboardPair(p) =>
+                write!(f, "Plugboard pair '{}' must consist of exactly two letters", p),
+            EnigmaError::PlugboardFull =>
+                write!(f, "Plugboard cannot hold more than 13 cables"),
+            EnigmaError::KeyLengthMismatch { expected, found } =>
+                write!(f, "Key length must match no. of rotors, expected {} letters but found {}", expected, found),
+            EnigmaError::InvalidRingSetting(r) =>
+                write!(f, "Invalid ring setting '{}', expected a letter A-Z or a number 01-26", r),
+            EnigmaError::RingSettingCountMismatch { expected, found } =>
+                write!(f, "Ring settings must match no. of rotors, expected {} but found {}", expected, found),
+            EnigmaError::MalformedConfig(m) =>
+                write!(f, "Malformed machine configuration: {}", m),
+            EnigmaError::InvalidKeySheet(m) =>
+                write!(f, "Cannot generate key sheet: {}", m),
+            EnigmaError::NoDailyKey { year, month, day } =>
+                write!(f, "Key sheet holds no key for {:04}-{:02}-{:02}", year, month, day),
+            EnigmaError::MalformedIndicator(m) =>
+                write!(f, "Malformed indicator: {}", m),
+            EnigmaError::MalformedMessage(m) =>
+                write!(f, "Malformed radio message: {}", m),
+            EnigmaError::InvalidBigramTable(m) =>
+                write!(f, "Invalid bigram table: {}", m),
+            EnigmaError::InvalidNgramCounts(m) =>
+                write!(f, "Invalid n-gram counts: {}", m),
+            EnigmaError::InvalidCrib(m) =>
+                write!(f, "Invalid crib: {}", m),
+            EnigmaError::CribCrash { position, letter } =>
+                write!(f, "Crib places '{}' against itself at position {}, which the reflector rules out", letter, position),
+            EnigmaError::PlugboardContradiction { letter } =>
+                write!(f, "No plugboard partner is left for '{}', so the crib cannot be right at this rotor setting", letter)
+        }
+    }
+}
+
+impl std::error::Error for EnigmaError {}
@@ -0,0 +1,242 @@
+use std::fmt;
+use std::str::FromStr;
+use rand::Rng;
+use crate::enigma::Enigma;
+use crate::error::EnigmaError;
+use crate::rotor::{try_alpha_index, ALPHA};
+
+// Procedure by which the message key was passed to the receiving operator
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Procedure {
+    // Until May 1940 the message key was typed twice at the Grundstellung
+    // from the key sheet, the six letters heading the message
+    Doubled,
+    // Afterwards the operator chose a Grundstellung for each message and
+    // sent it in clear followed by the message key typed once at it
+    Single
+}
+
+impl fmt::Display for Procedure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Procedure::Doubled => write!(f, "doubled"),
+            Procedure::Single => write!(f, "single")
+        }
+    }
+}
+
+impl FromStr for Procedure {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "doubled" | "pre1940" => Ok(Procedure::Doubled),
+            "single" | "post1940" => Ok(Procedure::Single),
+            _ => Err(format!("Unknown indicator procedure '{}', expected doubled or single", value))
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Indicator {
+    pub procedure: Procedure,
+    pub grundstellung: String,
+    pub enciphered_key: String
+}
+
+// Window letters for a machine, checked against its number of rotors
+fn try_key(machine: &Enigma, key: &str) -> Result<String, EnigmaError> {
+    let key = key.trim().to_ascii_uppercase();
+    let n_rotors = machine.get_rotor_ids().len();
+
+    if key.chars().count() != n_rotors {
+        return Err(EnigmaError::KeyLengthMismatch { expected: n_rotors, found: key.chars().count() });
+    }
+    for letter in key.chars() {
+        try_alpha_index(letter)?;
+    }
+
+    Ok(key)
+}
+
+pub fn random_key<R: Rng>(rng: &mut R, length: usize) -> String {
+    (0..length).map(|_| ALPHA[rng.gen_range(0..26)]).collect()
+}
+
+impl Indicator {
+    // Enciphers the message key at the Grundstellung on a copy of the daily machine
+    pub fn try_create(machine: &Enigma, procedure: Procedure, grundstellung: &str, message_key: &str) -> Result<Self, EnigmaError> {
+        let grundstellung = try_key(machine, grundstellung)?;
+        let message_key = try_key(machine, message_key)?;
+
+        let typed = match procedure {
+            Procedure::Doubled => message_key.repeat(2),
+            Procedure::Single => message_key
+        };
+
+        let mut machine = machine.clone();
+        machine.try_set_key(grundstellung.clone())?;
+
+        Ok(Self {
+            procedure,
+            enciphered_key: typed.chars().map(|x| machine.try_type_letter(x)).collect::<Result<String, EnigmaError>>()?,
+            grundstellung
+        })
+    }
+
+    pub fn try_recover_key(&self, machine: &Enigma) -> Result<String, EnigmaError> {
+        let mut machine = machine.clone();
+        machine.try_set_key(self.grundstellung.clone())?;
+
+        let typed = self.enciphered_key.chars()
+            .map(|x| machine.try_type_letter(x))
+            .collect::<Result<String, EnigmaError>>()?;
+
+        match self.procedure {
+            Procedure::Single => Ok(typed),
+            Procedure::Doubled => {
+                // A garbled indicator shows up as two differing halves
+                let (first, second) = typed.split_at(typed.len() / 2);
+                if first != second {
+                    return Err(EnigmaError::MalformedIndicator(format!(
+                        "enciphered key {} deciphers to {}, halves differ", self.enciphered_key, typed)));
+                }
+                Ok(first.to_string())
+            }
+        }
+    }
+
+    // Reads an indicator as sent, "WXC KCH" for the single procedure or the
+    // six letters "PEWWFT" for the doubled one, whose Grundstellung comes
+    // from the key sheet rather than the message
+    pub fn try_parse(text: &str, procedure: Procedure, grundstellung: Option<&str>) -> Result<Self, EnigmaError> {
+        let groups: Vec<String> = text.split_whitespace().map(|x| x.to_ascii_uppercase()).collect();
+
+        let (grundstellung, enciphered_key) = match (procedure, groups.len(), grundstellung) {
+            (Procedure::Single, 2, _) => (groups[0].clone(), groups[1].clone()),
+            (Procedure::Doubled, _, Some(g)) => (g.to_ascii_uppercase(), groups.concat()),
+            (Procedure::Doubled, _, None) => return Err(EnigmaError::MalformedIndicator(
+                "the doubled procedure requires the Grundstellung from the key sheet".to_string())),
+            _ => return Err(EnigmaError::MalformedIndicator(format!(
+                "expected a Grundstellung and enciphered key but found '{}'", text.trim())))
+        };
+
+        let expected = match procedure {
+            Procedure::Doubled => grundstellung.len() * 2,
+            Procedure::Single => grundstellung.len()
+        };
+
+        if enciphered_key.len() != expected || !enciphered_key.chars().all(|x| x.is_ascii_uppercase()) {
+            return Err(EnigmaError::MalformedIndicator(format!(
+                "expected an enciphered key of {} letters but found '{}'", expected, enciphered_key)));
+        }
+
+        Ok(Self { procedure, grundstellung, enciphered_key })
+    }
+}
+
+impl fmt::Display for Indicator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.procedure {
+            Procedure::Doubled => write!(f, "{}", self.enciphered_key),
+            Procedure::Single => write!(f, "{} {}", self.grundstellung, self.enciphered_key)
+        }
+    }
+}
+
+// Enciphers a message under a freshly drawn message key, returning the
+// indicator to send with it. Under the doubled procedure the Grundstellung
+// of the key sheet must be given, under the single procedure one is drawn
+// at random when none is given.
+pub fn try_encrypt_message<R: Rng>(
+    machine: &Enigma, procedure: Procedure, grundstellung: Option<&str>, plaintext: &str, rng: &mut R
+) -> Result<(Indicator, String), EnigmaError> {
+    let n_rotors = machine.get_rotor_ids().len();
+
+    let grundstellung = match (procedure, grundstellung) {
+        (_, Some(g)) => g.to_string(),
+        (Procedure::Single, None) => random_key(rng, n_rotors),
+        (Procedure::Doubled, None) => return Err(EnigmaError::MalformedIndicator(
+            "the doubled procedure requires the Grundstellung from the key sheet".to_string()))
+    };
+    let message_key = random_key(rng, n_rotors);
+
+    let indicator = Indicator::try_create(machine, procedure, &grundstellung, &message_key)?;
+
+    let mut machine = machine.clone();
+    machine.try_set_key(message_key)?;
+
+    Ok((indicator, machine.try_type_phrase(plaintext.to_string())?))
+}
+
+pub fn try_decrypt_message(machine: &Enigma, indicator: &Indicator, ciphertext: &str) -> Result<String, EnigmaError> {
+    let message_key = indicator.try_recover_key(machine)?;
+
+    let mut machine = machine.clone();
+    machine.try_set_key(message_key)?;
+    machine.try_decode_phrase(ciphertext)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use super::{Indicator, Procedure};
+    use crate::enigma::Enigma;
+    use crate::error::EnigmaError;
+
+    fn barbarossa() -> Enigma {
+        let mut machine = Enigma::new(vec![2, 4, 5], 'B', "M3".to_string());
+        machine.set_ring_settings("BUL");
+        machine.set_plugboard("AV BS CG DL FU HZ IN KM OW RX");
+        machine
+    }
+
+    #[test]
+    fn test_single_indicator() {
+        let machine = barbarossa();
+        let indicator = Indicator::try_parse("WXC KCH", Procedure::Single, None).unwrap();
+        assert!(indicator.try_recover_key(&machine) == Ok("BLA".to_string()));
+        assert!(indicator == Indicator::try_create(&machine, Procedure::Single, "WXC", "BLA").unwrap());
+        assert!(indicator.to_string() == "WXC KCH");
+
+        let message = super::try_decrypt_message(&machine, &indicator, "EDPUD NRGYS ZRCXN UYTPO MRMBO").unwrap();
+        assert!(message.starts_with("AUFKLXABTEILUNGXVONXKURTI"));
+    }
+
+    #[test]
+    fn test_doubled_indicator() {
+        let machine = barbarossa();
+        let indicator = Indicator::try_create(&machine, Procedure::Doubled, "JKL", "PQR").unwrap();
+        assert!(indicator.enciphered_key.len() == 6);
+
+        let parsed = Indicator::try_parse(&indicator.to_string(), Procedure::Doubled, Some("jkl")).unwrap();
+        assert!(parsed.try_recover_key(&machine) == Ok("PQR".to_string()));
+
+        // A corrupted letter breaks the repetition of the key
+        let mut garbled = parsed.clone();
+        garbled.enciphered_key.replace_range(4..5, if &parsed.enciphered_key[4..5] == "A" {"B"} else {"A"});
+        assert!(matches!(garbled.try_recover_key(&machine), Err(EnigmaError::MalformedIndicator(_))));
+
+        assert!(Indicator::try_parse("ABCDEF", Procedure::Doubled, None).is_err());
+        assert!(Indicator::try_parse("ABCDE", Procedure::Doubled, Some("JKL")).is_err());
+        assert!(Indicator::try_parse("WXC", Procedure::Single, None).is_err());
+        assert!(Indicator::try_create(&machine, Procedure::Single, "WX", "BLA").is_err());
+    }
+
+    #[test]
+    fn test_message_round_trip() {
+        let machine = barbarossa();
+        let mut rng = StdRng::seed_from_u64(1940);
+
+        for (procedure, grundstellung) in [(Procedure::Single, None), (Procedure::Doubled, Some("EMV"))] {
+            let (indicator, cipher) = super::try_encrypt_message(
+                &machine, procedure, grundstellung, "ANGRIFFXMORGENXFRUEH", &mut rng).unwrap();
+            assert!(super::try_decrypt_message(&machine, &indicator, &cipher) == Ok("ANGRIFFXMORGENXFRUEH".to_string()));
+        }
+
+        assert!(super::try_encrypt_message(&machine, Procedure::Doubled, None, "ANGRIFF", &mut rng).is_err());
+        assert!("pre1940".parse::<Procedure>() == Ok(Procedure::Doubled));
+        assert!("weekly".parse::<Procedure>().is_err());
+    }
+}
@@ -0,0 +1,351 @@
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use crate::config::MachineConfig;
+use crate::enigma::Enigma;
+use crate::error::EnigmaError;
+use crate::rotor::{self, ALPHA, BETA, GAMMA};
+
+// Attempts at drawing a wheel order or plugboard before giving up, the
+// constraints only failing to be met when too much is asked of them
+const MAX_ATTEMPTS: usize = 1000;
+
+// Parameters of a key list. The defaults follow the Heer and Luftwaffe
+// sheets of 1940 onwards: three of the five wheels I-V, reflector B,
+// ten plugboard cables and four Kenngruppen per day.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeySheetSpec {
+    pub model: String,
+    pub reflector: char,
+    pub rotor_pool: Vec<i32>,
+    pub plug_pairs: usize,
+    pub kenngruppen: usize
+}
+
+impl Default for KeySheetSpec {
+    fn default() -> Self {
+        Self {
+            model: "M3".to_string(),
+            reflector: 'B',
+            rotor_pool: vec![1, 2, 3, 4, 5],
+            plug_pairs: 10,
+            kenngruppen: 4
+        }
+    }
+}
+
+// Settings for a single day of the month
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DailyKey {
+    pub day: u32,
+    pub rotors: Vec<String>,
+    pub rings: String,
+    pub plugboard: String,
+    pub kenngruppen: Vec<String>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeySheet {
+    pub model: String,
+    pub reflector: char,
+    pub year: u32,
+    pub month: u32,
+    pub days: Vec<DailyKey>
+}
+
+pub fn days_in_month(year: u32, month: u32) -> u32 {
+    match month {
+        4 | 6 | 9 | 11 => 30,
+        2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => 29,
+        2 => 28,
+        _ => 31
+    }
+}
+
+// Draws a wheel order which leaves none of the three moving wheels in the
+// slot it held the day before. The Greek wheel of the M4 is drawn freely.
+fn draw_rotors(rng: &mut StdRng, spec: &KeySheetSpec, previous: Option<&Vec<i32>>) -> Result<Vec<i32>, EnigmaError> {
+    let greek = spec.model == "M4";
+
+    for _i in 0..MAX_ATTEMPTS {
+        let mut rotors: Vec<i32> = spec.rotor_pool.choose_multiple(rng, 3).cloned().collect();
+
+        if greek {
+            rotors.insert(0, *[BETA, GAMMA].choose(rng).unwrap_or(&BETA));
+        }
+
+        let repeats = match previous {
+            Some(p) => p.iter().rev().zip(rotors.iter().rev()).take(3).any(|(a, b)| a == b),
+            None => false
+        };
+
+        if !repeats {
+            return Ok(rotors);
+        }
+    }
+
+    Err(EnigmaError::InvalidKeySheet(format!(
+        "cannot draw a new wheel order every day from rotors {:?}", spec.rotor_pool)))
+}
+
+// Cables never joined two letters adjacent in the alphabet, a rule
+// introduced to keep operators from choosing easy patterns
+fn draw_plugboard(rng: &mut StdRng, n_pairs: usize) -> Result<String, EnigmaError> {
+    for _i in 0..MAX_ATTEMPTS {
+        let mut letters = ALPHA.to_vec();
+        letters.shuffle(rng);
+
+        let mut pairs: Vec<(char, char)> = letters.chunks(2)
+            .take(n_pairs)
+            .map(|x| if x[0] < x[1] {(x[0], x[1])} else {(x[1], x[0])})
+            .collect();
+
+        if pairs.iter().all(|(a, b)| *b as u8 - *a as u8 != 1) {
+            pairs.sort();
+            return Ok(pairs.iter().map(|(a, b)| format!("{}{}", a, b)).collect::<Vec<String>>().join(" "));
+        }
+    }
+
+    Err(EnigmaError::InvalidKeySheet(format!(
+        "cannot draw {} plugboard cables without joining neighbouring letters", n_pairs)))
+}
+
+// Every possible Kenngruppe in a random order, to be dealt out in turn
+fn shuffled_groups(rng: &mut StdRng) -> Vec<String> {
+    let mut groups: Vec<String> = (0..26 * 26 * 26)
+        .map(|x: usize| [x / 676, x / 26 % 26, x % 26].iter().map(|&i| ALPHA[i].to_ascii_lowercase()).collect())
+        .collect();
+    groups.shuffle(rng);
+    groups
+}
+
+impl KeySheet {
+    pub fn try_generate(year: u32, month: u32, seed: u64, spec: &KeySheetSpec) -> Result<Self, EnigmaError> {
+        if !(1..=12).contains(&month) {
+            return Err(EnigmaError::InvalidKeySheet(format!("invalid month {}", month)));
+        }
+        if spec.plug_pairs > crate::plugboard::MAX_PAIRS {
+            return Err(EnigmaError::PlugboardFull);
+        }
+        if spec.kenngruppen * days_in_month(year, month) as usize > 26usize.pow(3) {
+            return Err(EnigmaError::InvalidKeySheet(format!(
+                "{} Kenngruppen a day cannot all differ over a month", spec.kenngruppen)));
+        }
+
+        // The moving wheels are drawn from the pool, so every wheel of it must
+        // be one the machine could take there
+        for (i, rotor_id) in spec.rotor_pool.iter().enumerate() {
+            if spec.rotor_pool[..i].contains(rotor_id) {
+                return Err(EnigmaError::DuplicateRotor(*rotor_id));
+            }
+            if rotor::try_rotors(*rotor_id)?.is_greek() {
+                return Err(EnigmaError::MisplacedRotor { rotor: *rotor_id, position: "moving".to_string() });
+            }
+        }
+        if spec.rotor_pool.len() < 3 {
+            return Err(EnigmaError::InvalidKeySheet(format!(
+                "a pool of {} rotors cannot fill the three moving wheels", spec.rotor_pool.len())));
+        }
+
+        // Checks the model and reflector once up front
+        let mut rotor_list = spec.rotor_pool.iter().take(3).cloned().collect::<Vec<i32>>();
+        if spec.model == "M4" {
+            rotor_list.insert(0, BETA);
+        }
+        Enigma::try_new(rotor_list, spec.reflector, spec.model.clone())?;
+
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut days = Vec::new();
+        let mut previous: Option<Vec<i32>> = None;
+        let mut groups = shuffled_groups(&mut rng).into_iter();
+
+        for day in 1..=days_in_month(year, month) {
+            let rotors = draw_rotors(&mut rng, spec, previous.as_ref())?;
+            let rings: Vec<String> = rotors.iter().map(|_| format!("{:02}", rng.gen_range(1..=26))).collect();
+            let plugboard = draw_plugboard(&mut rng, spec.plug_pairs)?;
+
+            // Kenngruppen identify the key in use and so are never repeated within a month
+            let kenngruppen: Vec<String> = groups.by_ref().take(spec.kenngruppen).collect();
+
+            days.push(DailyKey {
+                day,
+                rotors: rotors.iter().map(|x| rotor::try_rotors(*x).map(|r| r.get_name())).collect::<Result<Vec<String>, EnigmaError>>()?,
+                rings: rings.join(" "),
+                plugboard,
+                kenngruppen
+            });
+
+            previous = Some(rotors);
+        }
+
+        Ok(Self {
+            model: spec.model.clone(),
+            reflector: spec.reflector,
+            year,
+            month,
+            days
+        })
+    }
+
+    pub fn generate(year: u32, month: u32, seed: u64) -> Self {
+        match Self::try_generate(year, month, seed, &KeySheetSpec::default()) {
+            Ok(k) => k,
+            Err(e) => panic!("{}", e)
+        }
+    }
+
+    pub fn get_day(&self, day: u32) -> Option<&DailyKey> {
+        self.days.iter().find(|x| x.day == day)
+    }
+
+    // The start position is left at A, as it was chosen by the operator for each message
+    pub fn try_config_for(&self, year: u32, month: u32, day: u32) -> Result<MachineConfig, EnigmaError> {
+        let key = match self.get_day(day) {
+            Some(k) if year == self.year && month == self.month => k,
+            _ => return Err(EnigmaError::NoDailyKey { year, month, day })
+        };
+
+        Ok(MachineConfig {
+            model: self.model.clone(),
+            rotors: key.rotors.clone(),
+            reflector: self.reflector,
+            rings: key.rings.clone(),
+            plugboard: key.plugboard.clone(),
+            start: String::new()
+        })
+    }
+
+    pub fn try_machine_for(&self, year: u32, month: u32, day: u32) -> Result<Enigma, EnigmaError> {
+        Enigma::try_from_config(&self.try_config_for(year, month, day)?)
+    }
+
+    // Printable sheet with the last day of the month at the top, so that
+    // each day could be cut off and destroyed once it had been used
+    pub fn to_table(&self) -> String {
+        let rotor_width = self.days.iter().map(|x| x.rotors.join(" ").len()).max().unwrap_or(0).max(10);
+        let ring_width = self.days.iter().map(|x| x.rings.len()).max().unwrap_or(0).max(12);
+        let plug_width = self.days.iter().map(|x| x.plugboard.len()).max().unwrap_or(0).max(19);
+
+        let mut table = format!(
+            "Geheim! Maschinenschlüssel {} {:02}/{:04}, Umkehrwalze {}\n\n",
+            self.model, self.month, self.year, self.reflector);
+        let header = format!(
+            "{:>3} | {:<rw$} | {:<gw$} | {:<pw$} | {}",
+            "Tag", "Walzenlage", "Ringstellung", "Steckerverbindungen", "Kenngruppen",
+            rw = rotor_width, gw = ring_width, pw = plug_width);
+
+        table += &header;
+        table += "\n";
+        table += &"-".repeat(header.chars().count());
+        table += "\n";
+
+        for key in self.days.iter().rev() {
+            table += &format!(
+                "{:>3} | {:<rw$} | {:<gw$} | {:<pw$} | {}\n",
+                key.day, key.rotors.join(" "), key.rings, key.plugboard, key.kenngruppen.join(" "),
+                rw = rotor_width, gw = ring_width, pw = plug_width);
+        }
+
+        table
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Key sheet is always serialisable")
+    }
+
+    pub fn try_from_json(json: &str) -> Result<Self, EnigmaError> {
+        serde_json::from_str(json).map_err(|e| EnigmaError::MalformedConfig(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{KeySheet, KeySheetSpec};
+    use crate::error::EnigmaError;
+    use crate::rotor::BETA;
+
+    #[test]
+    fn test_days_in_month() {
+        assert!(super::days_in_month(1941, 7) == 31);
+        assert!(super::days_in_month(1941, 9) == 30);
+        assert!(super::days_in_month(1940, 2) == 29);
+        assert!(super::days_in_month(1900, 2) == 28);
+    }
+
+    #[test]
+    fn test_keysheet_constraints() {
+        let sheet = KeySheet::generate(1941, 7, 1941);
+        assert!(sheet.days.len() == 31);
+        assert!(sheet == KeySheet::generate(1941, 7, 1941));
+        assert!(sheet != KeySheet::generate(1941, 7, 1942));
+
+        for (yesterday, today) in sheet.days.iter().zip(sheet.days.iter().skip(1)) {
+            assert!(yesterday.rotors.iter().zip(today.rotors.iter()).all(|(a, b)| a != b));
+        }
+
+        let mut groups: Vec<&String> = sheet.days.iter().flat_map(|x| x.kenngruppen.iter()).collect();
+        groups.sort();
+        groups.dedup();
+        assert!(groups.len() == 31 * 4);
+
+        for key in &sheet.days {
+            let pairs: Vec<&str> = key.plugboard.split(' ').collect();
+            assert!(pairs.len() == 10);
+            assert!(pairs.iter().all(|x| x.as_bytes()[1] - x.as_bytes()[0] != 1));
+        }
+    }
+
+    #[test]
+    fn test_keysheet_machine() {
+        let sheet = KeySheet::try_from_json(&KeySheet::generate(1941, 7, 7).to_json()).unwrap();
+        let key = sheet.get_day(7).unwrap();
+        let machine = sheet.try_machine_for(1941, 7, 7).unwrap();
+
+        assert!(machine.get_rotor_names() == key.rotors);
+        assert!(machine.plugboard_pairs().len() == 10);
+        assert!(sheet.try_machine_for(1941, 8, 7).err()
+            == Some(crate::error::EnigmaError::NoDailyKey { year: 1941, month: 8, day: 7 }));
+        assert!(sheet.try_machine_for(1941, 7, 32).is_err());
+
+        let table = sheet.to_table();
+        assert!(table.lines().nth(4).unwrap().starts_with(" 31 |"));
+        assert!(table.contains(&key.kenngruppen.join(" ")));
+    }
+
+    #[test]
+    fn test_keysheet_m4() {
+        let spec = KeySheetSpec {
+            model: "M4".to_string(),
+            reflector: 'B',
+            rotor_pool: vec![1, 2, 3, 4, 5, 6, 7, 8],
+            ..KeySheetSpec::default()
+        };
+        let sheet = KeySheet::try_generate(1943, 2, 3, &spec).unwrap();
+        assert!(sheet.days.len() == 28);
+        assert!(sheet.days.iter().all(|x| x.rotors[0] == "Beta" || x.rotors[0] == "Gamma"));
+        assert!(sheet.try_machine_for(1943, 2, 14).is_ok());
+
+        let spec = KeySheetSpec { rotor_pool: vec![1, 2, 3], ..KeySheetSpec::default() };
+        assert!(KeySheet::try_generate(1941, 7, 1, &spec).is_ok());
+        assert!(KeySheet::try_generate(1941, 13, 1, &KeySheetSpec::default()).is_err());
+
+        // The whole pool is checked, not just the wheels of the first day
+        let pool = |rotor_pool: Vec<i32>| KeySheet::try_generate(1941, 7, 1, &KeySheetSpec { rotor_pool, ..KeySheetSpec::default() });
+        assert!(pool(vec![1, 2, 3, 4, 11]) == Err(EnigmaError::UnknownRotor(11)));
+        assert!(pool(vec![1, 2, 3, 4, 2]) == Err(EnigmaError::DuplicateRotor(2)));
+        assert!(matches!(pool(vec![1, 2, 3, BETA]), Err(EnigmaError::MisplacedRotor { rotor: BETA, .. })));
+        assert!(matches!(pool(vec![1, 2]), Err(EnigmaError::InvalidKeySheet(_))));
+
+        let spec = KeySheetSpec { kenngruppen: 600, ..KeySheetSpec::default() };
+        assert!(matches!(KeySheet::try_generate(1941, 7, 1, &spec), Err(EnigmaError::InvalidKeySheet(_))));
+
+        // A month using all but 30 of the possible Kenngruppen still finds them
+        let spec = KeySheetSpec { kenngruppen: 566, ..KeySheetSpec::default() };
+        let sheet = KeySheet::try_generate(1941, 7, 1, &spec).unwrap();
+        let mut groups: Vec<&String> = sheet.days.iter().flat_map(|x| x.kenngruppen.iter()).collect();
+        groups.sort();
+        groups.dedup();
+        assert!(groups.len() == 566 * 31);
+    }
+}
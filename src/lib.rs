@@ -0,0 +1,57 @@
+//! Simulation of the M3 and M4 variants of the Enigma machine.
+//!
+//! The machine itself is provided by [`Enigma`], built from the individual
+//! components found in the [`rotor`], [`reflector`] and [`plugboard`] modules.
+//! Preparation of plain text for the keyboard is handled by [`text`], and
+//! machine setups can be saved and loaded through the [`config`] module.
+//! Monthly key lists for exercises are produced by [`keysheet`], and the
+//! per-message keys sent alongside each message are handled by [`indicator`].
+//! Complete radio messages, split into parts, are composed and read by [`message`].
+//! The Kriegsmarine procedure of disguising indicators with bigram tables is in [`naval`],
+//! and the wheel order can be narrowed from messages in depth by [`banburismus`].
+//! Ciphertext-only recovery of machine settings is provided by [`attack`], with
+//! candidate decipherments scored by the language models in [`scoring`].
+//! Crib-based searches for the rotor settings are run on the simulated [`bombe`],
+//! with the places a crib can take found by [`crib`] and the plugboard then
+//! recovered by [`steckers`]. The Polish methods on doubled indicators are in
+//! [`cyclometer`] and [`zygalski`].
+
+pub mod attack;
+pub mod banburismus;
+pub mod bombe;
+pub mod config;
+pub mod crib;
+pub mod cyclometer;
+pub mod enigma;
+pub mod error;
+pub mod indicator;
+pub mod keysheet;
+pub mod message;
+pub mod naval;
+pub mod plugboard;
+pub mod reflector;
+pub mod rotor;
+pub mod scoring;
+pub mod steckers;
+pub mod text;
+pub mod zygalski;
+
+pub use attack::{AttackSpec, Candidate};
+pub use banburismus::{BanburismusSpec, Intercept};
+pub use bombe::{Menu, Stop};
+pub use config::{MachineConfig, MachineState};
+pub use crib::Placement;
+pub use cyclometer::{Catalogue, Characteristic, Permutations};
+pub use enigma::Enigma;
+pub use error::EnigmaError;
+pub use indicator::{Indicator, Procedure};
+pub use keysheet::{KeySheet, KeySheetSpec};
+pub use message::{MessagePart, RadioMessage};
+pub use naval::{BigramTable, NavalProcedure};
+pub use plugboard::Plugboard;
+pub use reflector::{try_reflectors, try_thin_reflectors, Reflector, Reflectors};
+pub use rotor::{alpha_index, try_alpha_index, try_rotor_id, try_rotors, Rotor, Rotors, ALPHA};
+pub use scoring::{IndexOfCoincidence, Language, NgramScorer, Scorer};
+pub use steckers::PlugboardSolution;
+pub use text::{InputPolicy, OutputFormat, Padding};
+pub use zygalski::{Sheet, SheetSolution};
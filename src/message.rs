@@ -0,0 +1,288 @@
+use std::fmt;
+use rand::Rng;
+use crate::enigma::Enigma;
+use crate::error::EnigmaError;
+use crate::indicator::{self, Indicator, Procedure};
+use crate::text;
+
+// Longest part sent as a single transmission, longer texts being split
+pub const MAX_PART_LETTERS: usize = 250;
+
+// One transmitted part, headed by a preamble in the form
+//
+//     1840 - 2TLE - 1TL - 179 - WXC KCH -
+//
+// giving the time of origin, number of parts (Teile), the part number
+// (Teil), the number of letters in the part and the indicator groups
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessagePart {
+    pub time: String,
+    pub parts: usize,
+    pub part: usize,
+    pub letters: usize,
+    pub indicator: Indicator,
+    pub text: String
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RadioMessage {
+    pub parts: Vec<MessagePart>
+}
+
+fn malformed(message: String) -> EnigmaError {
+    EnigmaError::MalformedMessage(message)
+}
+
+// Splits the letters of a message into the fewest parts within the limit,
+// keeping them close to equal length and to whole groups where possible
+fn split_parts(letters: &str, group_size: usize) -> Vec<String> {
+    let chars: Vec<char> = letters.chars().collect();
+    let n_letters = chars.iter().filter(|x| x.is_ascii_uppercase()).count();
+    // A group longer than a whole part cannot be kept together, so parts
+    // are then simply cut at the limit
+    let group_size = group_size.clamp(1, MAX_PART_LETTERS);
+
+    let limit = MAX_PART_LETTERS - MAX_PART_LETTERS % group_size;
+    let n_parts = n_letters.div_ceil(limit).max(1);
+    let part_size = n_letters.div_ceil(n_parts).div_ceil(group_size) * group_size;
+    let part_size = part_size.min(limit);
+
+    let mut parts = vec![String::new()];
+    let mut count = 0;
+
+    for letter in chars {
+        if letter.is_ascii_uppercase() {
+            if count == part_size {
+                parts.push(String::new());
+                count = 0;
+            }
+            count += 1;
+        }
+        if let Some(part) = parts.last_mut() {
+            part.push(letter);
+        }
+    }
+
+    parts
+}
+
+impl MessagePart {
+    fn preamble(&self) -> String {
+        format!("{} - {}TLE - {}TL - {} - {} -", self.time, self.parts, self.part, self.letters, self.indicator)
+    }
+
+    // Reads the fields of a preamble, accepting '-', '=' or '–' as separators
+    fn try_parse_preamble(line: &str, procedure: Procedure, grundstellung: Option<&str>) -> Result<(String, usize, usize, usize, Indicator), EnigmaError> {
+        let fields: Vec<String> = line.split(['-', '=', '–'])
+            .map(|x| x.split_whitespace().collect::<Vec<&str>>().join(" ").to_ascii_uppercase())
+            .filter(|x| !x.is_empty())
+            .collect();
+
+        if fields.len() != 5 {
+            return Err(malformed(format!("expected five preamble fields but found '{}'", line.trim())));
+        }
+
+        let number = |field: &str, suffix: &str| field.replace(' ', "").strip_suffix(suffix)
+            .and_then(|x| x.parse::<usize>().ok())
+            .ok_or_else(|| malformed(format!("invalid preamble field '{}'", field)));
+
+        let letters = fields[3].parse::<usize>()
+            .map_err(|_| malformed(format!("invalid letter count '{}'", fields[3])))?;
+
+        Ok((
+            fields[0].clone(),
+            number(&fields[1], "TLE")?,
+            number(&fields[2], "TL")?,
+            letters,
+            Indicator::try_parse(&fields[4], procedure, grundstellung)?
+        ))
+    }
+}
+
+impl fmt::Display for MessagePart {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}\n{}", self.preamble(), self.text)
+    }
+}
+
+impl RadioMessage {
+    // Enciphers a message for transmission, each part under its own message
+    // key as laid down for the procedure. The text is grouped and padded as
+    // set by the output format of the machine.
+    pub fn try_compose<R: Rng>(
+        machine: &Enigma, procedure: Procedure, grundstellung: Option<&str>, time: &str, plaintext: &str, rng: &mut R
+    ) -> Result<Self, EnigmaError> {
+        // A part cannot hold a group longer than itself, so such groups are
+        // cut down to the part limit before the last one of a part is padded
+        let mut machine = machine.clone();
+        let mut format = machine.get_output_format();
+        format.group_size = format.group_size.min(MAX_PART_LETTERS);
+        machine.set_output_format(format);
+
+        let letters = text::try_prepare(plaintext, machine.get_input_policy())?;
+        let chunks = split_parts(&letters, format.group_size);
+        let n_parts = chunks.len();
+
+        let mut parts = Vec::with_capacity(n_parts);
+
+        for (i, chunk) in chunks.iter().enumerate() {
+            let (indicator, cipher) = indicator::try_encrypt_message(&machine, procedure, grundstellung, chunk, rng)?;
+
+            parts.push(MessagePart {
+                time: time.to_string(),
+                parts: n_parts,
+                part: i + 1,
+                letters: cipher.chars().filter(|x| x.is_ascii_uppercase()).count(),
+                indicator,
+                text: cipher
+            });
+        }
+
+        Ok(Self { parts })
+    }
+
+    // Reads a received message made up of one or more parts, each starting
+    // with its preamble line and followed by its lines of groups
+    pub fn try_parse(message: &str, procedure: Procedure, grundstellung: Option<&str>) -> Result<Self, EnigmaError> {
+        let mut parts: Vec<MessagePart> = Vec::new();
+
+        for line in message.lines().map(|x| x.trim()).filter(|x| !x.is_empty()) {
+            if line.contains(['-', '=', '–']) {
+                let (time, n_parts, part, letters, indicator) = MessagePart::try_parse_preamble(line, procedure, grundstellung)?;
+                parts.push(MessagePart { time, parts: n_parts, part, letters, indicator, text: String::new() });
+                continue;
+            }
+
+            match parts.last_mut() {
+                Some(part) => {
+                    if !part.text.is_empty() {
+                        part.text.push(' ');
+                    }
+                    part.text.push_str(line);
+                },
+                None => return Err(malformed(format!("text '{}' found before any preamble", line)))
+            }
+        }
+
+        if parts.is_empty() {
+            return Err(malformed("no preamble found".to_string()));
+        }
+
+        Ok(Self { parts })
+    }
+
+    // Puts the parts back in order, checking none is missing or damaged, and
+    // deciphers each under the message key recovered from its indicator
+    pub fn try_decrypt(&self, machine: &Enigma) -> Result<String, EnigmaError> {
+        let mut parts: Vec<&MessagePart> = self.parts.iter().collect();
+        parts.sort_by_key(|x| x.part);
+
+        let n_parts = match parts.first() {
+            Some(first) => first.parts,
+            None => return Err(malformed("message has no parts".to_string()))
+        };
+
+        for (i, part) in parts.iter().enumerate() {
+            if part.part != i + 1 || part.parts != n_parts {
+                return Err(malformed(format!("expected part {} of {}", i + 1, n_parts)));
+            }
+
+            let received = part.text.chars().filter(|x| x.is_ascii_uppercase()).count();
+            if received != part.letters {
+                return Err(malformed(format!(
+                    "part {} announces {} letters but {} were received", part.part, part.letters, received)));
+            }
+        }
+
+        if parts.len() != n_parts {
+            return Err(malformed(format!("expected {} parts but found {}", n_parts, parts.len())));
+        }
+
+        parts.iter()
+            .map(|x| indicator::try_decrypt_message(machine, &x.indicator, &x.text))
+            .collect()
+    }
+}
+
+impl fmt::Display for RadioMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts: Vec<String> = self.parts.iter().map(|x| x.to_string()).collect();
+        write!(f, "{}", parts.join("\n\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use super::RadioMessage;
+    use crate::enigma::Enigma;
+    use crate::error::EnigmaError;
+    use crate::indicator::Procedure;
+    use crate::text::{OutputFormat, Padding};
+
+    fn machine() -> Enigma {
+        let mut machine = Enigma::new(vec![2, 4, 5], 'B', "M3".to_string());
+        machine.set_ring_settings("BUL");
+        machine.set_plugboard("AV BS CG DL FU HZ IN KM OW RX");
+        machine
+    }
+
+    #[test]
+    fn test_split_parts() {
+        assert!(super::split_parts("ABCDEFG", 5) == vec!["ABCDEFG".to_string()]);
+
+        let parts = super::split_parts(&"A".repeat(251), 5);
+        assert!(parts.iter().map(|x| x.len()).collect::<Vec<usize>>() == vec![130, 121]);
+
+        let parts = super::split_parts(&"A".repeat(600), 5);
+        assert!(parts.iter().all(|x| x.len() <= super::MAX_PART_LETTERS));
+        assert!(parts.len() == 3);
+
+        let parts = super::split_parts(&"A".repeat(600), 300);
+        assert!(parts.iter().map(|x| x.len()).collect::<Vec<usize>>() == vec![250, 250, 100]);
+    }
+
+    #[test]
+    fn test_radio_message_round_trip() {
+        let mut machine = machine();
+        machine.set_output_format(OutputFormat { group_size: 5, padding: Padding::None });
+
+        let plaintext = "AUFKLXABTEILUNGXVONXKURTINOWAXKURTINOWAXNORDWESTLXSEBEZX".repeat(8);
+        let mut rng = StdRng::seed_from_u64(1941);
+        let message = RadioMessage::try_compose(&machine, Procedure::Single, None, "1840", &plaintext, &mut rng).unwrap();
+
+        assert!(message.parts.len() == 2);
+        assert!(message.parts.iter().all(|x| x.letters < super::MAX_PART_LETTERS));
+        assert!(message.parts.iter().map(|x| x.letters).sum::<usize>() == plaintext.len());
+        assert!(message.parts[0].indicator != message.parts[1].indicator);
+
+        let transmitted = message.to_string();
+        assert!(transmitted.lines().next().unwrap().starts_with("1840 - 2TLE - 1TL - 225 - "));
+
+        // Parts may be received out of order
+        let reordered = format!("{}\n\n{}", message.parts[1], message.parts[0]);
+        let received = RadioMessage::try_parse(&reordered, Procedure::Single, None).unwrap();
+        assert!(received.try_decrypt(&machine) == Ok(plaintext));
+    }
+
+    #[test]
+    fn test_radio_message_parse() {
+        let text = "1840 – 2 TLE – 1TL – 10 – WXC KCH –\nEDPUD NRGYS\n\n1840 = 2TLE = 2TL = 5 = WXC KCH =\nEDPUD";
+        let message = RadioMessage::try_parse(text, Procedure::Single, None).unwrap();
+        assert!(message.parts.len() == 2 && message.parts[0].text == "EDPUD NRGYS");
+        assert!(message.try_decrypt(&machine()) == Ok("AUFKLXABTEAUFKL".to_string()));
+
+        let missing = RadioMessage::try_parse(&text[..text.find("\n\n").unwrap()], Procedure::Single, None).unwrap();
+        assert!(matches!(missing.try_decrypt(&machine()), Err(EnigmaError::MalformedMessage(_))));
+
+        let short = RadioMessage::try_parse("1840 - 1TLE - 1TL - 12 - WXC KCH -\nEDPUD NRGYS", Procedure::Single, None).unwrap();
+        assert!(matches!(short.try_decrypt(&machine()), Err(EnigmaError::MalformedMessage(_))));
+
+        let empty = RadioMessage { parts: Vec::new() };
+        assert!(matches!(empty.try_decrypt(&machine()), Err(EnigmaError::MalformedMessage(_))));
+
+        assert!(RadioMessage::try_parse("EDPUD NRGYS", Procedure::Single, None).is_err());
+        assert!(RadioMessage::try_parse("1840 - 1TLE - 1TL - WXC KCH -", Procedure::Single, None).is_err());
+    }
+}
@@ -0,0 +1,353 @@
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use crate::enigma::Enigma;
+use crate::error::EnigmaError;
+use crate::indicator::random_key;
+use crate::rotor::{try_alpha_index, ALPHA};
+use crate::text::OutputFormat;
+
+const N_BIGRAMS: usize = 26 * 26;
+const UNSET: u16 = u16::MAX;
+
+// Naval messages were written in groups of four letters
+const NAVAL_GROUP_SIZE: usize = 4;
+
+fn try_bigram_index(bigram: &str) -> Result<usize, EnigmaError> {
+    let letters: Vec<char> = bigram.trim().to_ascii_uppercase().chars().collect();
+
+    if letters.len() != 2 {
+        return Err(EnigmaError::InvalidBigramTable(format!("'{}' is not a pair of letters", bigram.trim())));
+    }
+
+    Ok(try_alpha_index(letters[0])? * 26 + try_alpha_index(letters[1])?)
+}
+
+fn bigram(index: usize) -> String {
+    format!("{}{}", ALPHA[index / 26], ALPHA[index % 26])
+}
+
+// Doppelbuchstabentauschtafel, a reciprocal substitution of letter pairs
+// used to disguise the indicator groups of naval messages. Tables may be
+// loaded incompletely, in which case only the pairs given can be used.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BigramTable {
+    substitution: Vec<u16>
+}
+
+impl BigramTable {
+    fn empty() -> Self {
+        Self {
+            substitution: vec![UNSET; N_BIGRAMS]
+        }
+    }
+
+    // A complete table pairing every bigram with another at random
+    pub fn generate(seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut bigrams: Vec<u16> = (0..N_BIGRAMS as u16).collect();
+        bigrams.shuffle(&mut rng);
+
+        let mut table = Self::empty();
+        for pair in bigrams.chunks(2) {
+            table.substitution[pair[0] as usize] = pair[1];
+            table.substitution[pair[1] as usize] = pair[0];
+        }
+        table
+    }
+
+    // Reads entries of the form "AB=XY" or "AB XY", whitespace or comma
+    // separated, each entry also giving the reverse substitution
+    pub fn try_from_text(text: &str) -> Result<Self, EnigmaError> {
+        let mut table = Self::empty();
+        let tokens: Vec<&str> = text.lines()
+            .map(|x| x.split('#').next().unwrap_or(""))
+            .flat_map(|x| x.split([',', ' ', '\t', '=']))
+            .filter(|x| !x.is_empty())
+            .collect();
+
+        if !tokens.len().is_multiple_of(2) {
+            return Err(EnigmaError::InvalidBigramTable(format!("'{}' has no partner", tokens[tokens.len() - 1])));
+        }
+
+        for entry in tokens.chunks(2) {
+            let a = try_bigram_index(entry[0])?;
+            let b = try_bigram_index(entry[1])?;
+
+            let conflict = |x: usize, y: usize| table.substitution[x] != UNSET && table.substitution[x] as usize != y;
+            if a == b || conflict(a, b) || conflict(b, a) {
+                return Err(EnigmaError::InvalidBigramTable(format!("conflicting entry {}={}", entry[0], entry[1])));
+            }
+
+            table.substitution[a] = b as u16;
+            table.substitution[b] = a as u16;
+        }
+
+        Ok(table)
+    }
+
+    // Twenty-six lines of entries, each pair listed once
+    pub fn to_text(&self) -> String {
+        let mut lines = Vec::new();
+
+        for first in 0..26 {
+            let entries: Vec<String> = (first * 26..(first + 1) * 26)
+                .filter(|&x| self.substitution[x] != UNSET && (self.substitution[x] as usize) > x)
+                .map(|x| format!("{}={}", bigram(x), bigram(self.substitution[x] as usize)))
+                .collect();
+
+            if !entries.is_empty() {
+                lines.push(entries.join(" "));
+            }
+        }
+
+        lines.join("\n") + "\n"
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.substitution.iter().all(|&x| x != UNSET)
+    }
+
+    pub fn try_substitute(&self, pair: &str) -> Result<String, EnigmaError> {
+        match self.substitution[try_bigram_index(pair)?] {
+            UNSET => Err(EnigmaError::InvalidBigramTable(format!("no entry for {}", pair.to_ascii_uppercase()))),
+            x => Ok(bigram(x as usize))
+        }
+    }
+}
+
+fn try_trigram(trigram: &str) -> Result<Vec<char>, EnigmaError> {
+    let letters: Vec<char> = trigram.trim().to_ascii_uppercase().chars().collect();
+
+    if letters.len() != 3 {
+        return Err(EnigmaError::MalformedIndicator(format!("'{}' is not a trigram", trigram.trim())));
+    }
+    for letter in &letters {
+        try_alpha_index(*letter)?;
+    }
+
+    Ok(letters)
+}
+
+// Disguises the two trigrams from the Kenngruppenbuch. They are written one
+// above the other, offset by a letter with the gaps filled at random,
+//
+//     x S S S
+//     V V V x
+//
+// and the four vertical pairs replaced from the bigram table, the rows of
+// the result giving the two indicator groups.
+pub fn try_encode_indicator<R: Rng>(
+    table: &BigramTable, schluesselkenngruppe: &str, verfahrenkenngruppe: &str, rng: &mut R
+) -> Result<(String, String), EnigmaError> {
+    let mut top = random_key(rng, 1).chars().collect::<Vec<char>>();
+    top.extend(try_trigram(schluesselkenngruppe)?);
+
+    let mut bottom = try_trigram(verfahrenkenngruppe)?;
+    bottom.extend(random_key(rng, 1).chars());
+
+    let mut first = String::new();
+    let mut second = String::new();
+
+    for (a, b) in top.iter().zip(bottom.iter()) {
+        let substituted: Vec<char> = table.try_substitute(&format!("{}{}", a, b))?.chars().collect();
+        first.push(substituted[0]);
+        second.push(substituted[1]);
+    }
+
+    Ok((first, second))
+}
+
+// Reverses the disguise, returning the Schlüsselkenngruppe identifying the
+// key net and the Verfahrenkenngruppe from which the message key is found
+pub fn try_decode_indicator(table: &BigramTable, first: &str, second: &str) -> Result<(String, String), EnigmaError> {
+    let first: Vec<char> = first.trim().to_ascii_uppercase().chars().collect();
+    let second: Vec<char> = second.trim().to_ascii_uppercase().chars().collect();
+
+    if first.len() != 4 || second.len() != 4 {
+        return Err(EnigmaError::MalformedIndicator("expected two groups of four letters".to_string()));
+    }
+
+    let mut top = String::new();
+    let mut bottom = String::new();
+
+    for (a, b) in first.iter().zip(second.iter()) {
+        let original: Vec<char> = table.try_substitute(&format!("{}{}", a, b))?.chars().collect();
+        top.push(original[0]);
+        bottom.push(original[1]);
+    }
+
+    Ok((top[1..].to_string(), bottom[..3].to_string()))
+}
+
+// Daily naval settings for the indicator: the bigram tables in force and
+// the Grundstellung from the key sheet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NavalProcedure {
+    pub table: BigramTable,
+    pub grundstellung: String
+}
+
+impl NavalProcedure {
+    // The Verfahrenkenngruppe typed at the Grundstellung gives the message
+    // key for the three moving rotors. The Greek wheel of the M4 does not
+    // step, so it stays at the position given by the Grundstellung.
+    pub fn try_message_key(&self, machine: &Enigma, verfahrenkenngruppe: &str) -> Result<String, EnigmaError> {
+        let grundstellung = self.grundstellung.trim().to_ascii_uppercase();
+
+        let mut machine = machine.clone();
+        machine.try_set_key(grundstellung.clone())?;
+
+        let greek = &grundstellung[..grundstellung.len() - 3];
+
+        let typed = try_trigram(verfahrenkenngruppe)?.iter()
+            .map(|x| machine.try_type_letter(*x))
+            .collect::<Result<String, EnigmaError>>()?;
+
+        Ok(format!("{}{}", greek, typed))
+    }
+
+    // Enciphers a message in four letter groups, with the indicator groups
+    // heading the message and repeated at its end
+    pub fn try_encrypt<R: Rng>(
+        &self, machine: &Enigma, schluesselkenngruppe: &str, verfahrenkenngruppe: &str, plaintext: &str, rng: &mut R
+    ) -> Result<String, EnigmaError> {
+        let (first, second) = try_encode_indicator(&self.table, schluesselkenngruppe, verfahrenkenngruppe, rng)?;
+
+        let mut machine = machine.clone();
+        machine.try_set_key(self.try_message_key(&machine, verfahrenkenngruppe)?)?;
+        machine.set_output_format(OutputFormat { group_size: NAVAL_GROUP_SIZE, ..machine.get_output_format() });
+
+        let cipher = machine.try_type_phrase(plaintext.to_string())?;
+        Ok(format!("{} {} {} {} {}", first, second, cipher, first, second))
+    }
+
+    pub fn try_decrypt(&self, machine: &Enigma, message: &str) -> Result<String, EnigmaError> {
+        let groups: Vec<&str> = message.split_whitespace().collect();
+
+        if groups.len() < 4 {
+            return Err(EnigmaError::MalformedIndicator("message is too short to hold its indicator groups".to_string()));
+        }
+
+        let n_groups = groups.len();
+        if groups[..2] != groups[n_groups - 2..] {
+            return Err(EnigmaError::MalformedIndicator(format!(
+                "indicator groups {} {} are not repeated at the end of the message", groups[0], groups[1])));
+        }
+
+        let (_, verfahrenkenngruppe) = try_decode_indicator(&self.table, groups[0], groups[1])?;
+
+        let mut machine = machine.clone();
+        machine.try_set_key(self.try_message_key(&machine, &verfahrenkenngruppe)?)?;
+        machine.try_decode_phrase(&groups[2..n_groups - 2].concat())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use super::{BigramTable, NavalProcedure};
+    use crate::enigma::Enigma;
+    use crate::rotor::BETA;
+
+    #[test]
+    fn test_bigram_table() {
+        let table = BigramTable::generate(1942);
+        assert!(table.is_complete());
+        assert!(table == BigramTable::try_from_text(&table.to_text()).unwrap());
+
+        for pair in ["AA", "QZ", "ZA"] {
+            let substituted = table.try_substitute(pair).unwrap();
+            assert!(substituted != pair && table.try_substitute(&substituted).unwrap() == pair);
+        }
+
+        let partial = BigramTable::try_from_text("AB=XY, CD GH\nef=IJ # comment").unwrap();
+        assert!(!partial.is_complete());
+        assert!(partial.try_substitute("gh") == Ok("CD".to_string()));
+        assert!(partial.try_substitute("AA").is_err());
+
+        assert!(BigramTable::try_from_text("AB=XY AB=CD").is_err());
+        assert!(BigramTable::try_from_text("AB=AB").is_err());
+        assert!(BigramTable::try_from_text("AB=XY CD").is_err());
+        assert!(BigramTable::try_from_text("ABC=XY").is_err());
+    }
+
+    // No published bigram table or worked indicator example is to hand, so
+    // the tables here are made up and the layout follows the description of
+    // the procedure
+    #[test]
+    fn test_indicator_layout() {
+        // Hand-made substitutions for the vertical pairs of
+        //     Q U H M
+        //     S W Q P
+        // sending them as the groups REKB DRJO
+        let table = BigramTable::try_from_text("QS=RD UW=ER HQ=KJ MP=BO").unwrap();
+        assert!(super::try_decode_indicator(&table, "REKB", "DRJO") == Ok(("UHM".to_string(), "SWQ".to_string())));
+        assert!(super::try_decode_indicator(&table, "rekb", "drjo") == Ok(("UHM".to_string(), "SWQ".to_string())));
+        assert!(super::try_decode_indicator(&table, "REK", "DRJO").is_err());
+        assert!(super::try_decode_indicator(&table, "AEKB", "DRJO").is_err());
+
+        let table = BigramTable::generate(1943);
+        let mut rng = StdRng::seed_from_u64(0);
+        let (first, second) = super::try_encode_indicator(&table, "uhm", "SWQ", &mut rng).unwrap();
+        assert!(first.len() == 4 && second.len() == 4);
+        assert!(super::try_decode_indicator(&table, &first, &second) == Ok(("UHM".to_string(), "SWQ".to_string())));
+        assert!(super::try_encode_indicator(&table, "UH", "SWQ", &mut rng).is_err());
+    }
+
+    #[test]
+    fn test_naval_round_trip() {
+        let mut machine = Enigma::new(vec![BETA, 5, 6, 8], 'B', "M4".to_string());
+        machine.set_ring_settings("AEZM");
+        machine.set_plugboard("AE BF CM DQ HU JN LX PR SZ VW");
+
+        let procedure = NavalProcedure { table: BigramTable::generate(7), grundstellung: "RKDF".to_string() };
+        let key = procedure.try_message_key(&machine, "SWQ").unwrap();
+        assert!(key.len() == 4 && key.starts_with('R'));
+
+        let mut rng = StdRng::seed_from_u64(42);
+        let message = procedure.try_encrypt(&machine, "UHM", "SWQ", "VONVONJLOOKS", &mut rng).unwrap();
+        let groups: Vec<&str> = message.split(' ').collect();
+        assert!(groups.iter().all(|x| x.len() == 4));
+        assert!(groups[..2] == groups[groups.len() - 2..]);
+
+        assert!(procedure.try_decrypt(&machine, &message) == Ok("VONVONJLOOKS".to_string()));
+        assert!(procedure.try_decrypt(&machine, &message[..message.len() - 5]).is_err());
+    }
+
+    // Signal to U-534 as published with its settings, message key and
+    // ciphertext, which checks the machine but not the indicator groups.
+    // Neither the bigram tables nor the Grundstellung of the day are known,
+    // so a table is generated and the Verfahrenkenngruppe is the one that
+    // gives the published message key at the Grundstellung VAAA.
+    const U534_CIPHERTEXT: &str = "NCZW VUSX PNYM INHZ XMQX SFWX WLKJ AHSH NMCO CCAK UQPM KCSM HKSE INJU \
+        SBLK IOSX CKUB HMLL XCSJ USRR DVKO HULX WCCB GVLI YXEO AHXR HKKF VDRE WEZL XOBA FGYU JQUK GRTV \
+        UKAM EURB VEKS UHHV OYHA BCJW MAKL FKLM YFVN RIZR VVRT KOFD ANJM OLBG FFLE OPRG TFLV RHOW OPBE \
+        KVWM UQFM PWPA RMFH AGKX IIBG";
+    const U534_PLAINTEXT: &str = "VONVONJLOOKSJHFFTTTEINSEINSDREIZWOYYQNNSNEUNINHALTXXBEIANGRIFFUNTERWASSERGEDRUECKTYWABOSX\
+        LETZTERGEGNERSTANDNULACHTDREINULUHRMARQUANTONJOTANEUNACHTSEYHSDREIYZWOZWONULGRADYACHTSMYSTOSSENACHXEKNS\
+        VIERMBFAELLTYNNNNNNOOOVIERYSICHTEINSNULL";
+
+    #[test]
+    fn test_u534_signal() {
+        let mut machine = Enigma::new(vec![BETA, 2, 4, 1], 'B', "M4".to_string());
+        machine.set_ring_settings("AAAV");
+        machine.set_plugboard("AT BL DF GJ HM NW OP QY RZ VX");
+
+        let mut published = machine.clone();
+        published.set_key("VJNA".to_string());
+        assert!(published.decode_phrase(U534_CIPHERTEXT) == U534_PLAINTEXT);
+
+        // The Verfahrenkenngruppe typed at the Grundstellung gives the key,
+        // and the key typed there gives it back
+        let procedure = NavalProcedure { table: BigramTable::generate(1945), grundstellung: "VAAA".to_string() };
+        let verfahrenkenngruppe = procedure.try_message_key(&machine, "JNA").unwrap()[1..].to_string();
+        assert!(procedure.try_message_key(&machine, &verfahrenkenngruppe) == Ok("VJNA".to_string()));
+
+        let mut rng = StdRng::seed_from_u64(534);
+        let (first, second) = super::try_encode_indicator(&procedure.table, "KHZ", &verfahrenkenngruppe, &mut rng).unwrap();
+        let message = format!("{} {} {} {} {}", first, second, U534_CIPHERTEXT, first, second);
+        assert!(procedure.try_decrypt(&machine, &message) == Ok(U534_PLAINTEXT.to_string()));
+    }
+}
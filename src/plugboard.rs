@@ -1,62 +1,216 @@
-use std::collections::HashMap;
+use std::fmt;
+use crate::error::EnigmaError;
+use crate::rotor::{alpha_index, try_alpha_index, ALPHA};
+
+pub const MAX_PAIRS: usize = 13;
+
+const IDENTITY: [u8; 26] = [
+    0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12,
+    13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25
+];
 
 pub struct Plugboard {
-    conversions: HashMap<char, char>
+    wiring: [u8; 26]
 }
 
 impl Clone for Plugboard {
     fn clone(&self) -> Self {
         Self {
-            conversions: self.conversions.clone()
+            wiring: self.wiring
         }
     }
 }
 
+impl Default for Plugboard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for Plugboard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pairs: Vec<String> = self.pairs().iter().map(|(a, b)| format!("{}{}", a, b)).collect();
+        write!(f, "{}", pairs.join(" "))
+    }
+}
+
 impl Plugboard {
     pub fn new() -> Self {
         Self {
-            conversions: [
-                ('A', 'Z'), ('B', 'P'), ('C', 'M'),
-                ('D', 'S'), ('E', 'Y'), ('F', 'U'),
-                ('G', 'N'), ('H', 'V'), ('I', 'Q'),
-                ('J', 'X'), ('K', 'T'), ('L', 'R'),
-                ('M', 'C'), ('N', 'G'), ('O', 'W'),
-                ('P', 'B'), ('Q', 'I'), ('R', 'L'),
-                ('S', 'D'), ('T', 'K'), ('U', 'F'),
-                ('V', 'H'), ('W', 'O'), ('X', 'J'),
-                ('Y', 'E'), ('Z', 'A')
-            ].iter().cloned().collect()
+            wiring: IDENTITY
         }
     }
 
+    // Steckerbrett pairs in the notation of the key sheets, e.g. "AB CD EF"
+    pub fn try_from_pairs(pairs: &str) -> Result<Self, EnigmaError> {
+        let mut plugboard = Self::new();
+
+        for pair in pairs.split_whitespace() {
+            let letters: Vec<char> = pair.to_ascii_uppercase().chars().collect();
+
+            if letters.len() != 2 {
+                return Err(EnigmaError::MalformedPlugboardPair(pair.to_string()));
+            }
+
+            plugboard.try_connect(letters[0], letters[1])?;
+        }
+
+        Ok(plugboard)
+    }
+
+    pub fn from_pairs(pairs: &str) -> Self {
+        match Self::try_from_pairs(pairs) {
+            Ok(p) => p,
+            Err(e) => panic!("{}", e)
+        }
+    }
+
+    // The Steckerbrett is an involution, so the same table serves both directions
+    #[inline]
+    pub fn swap(&self, index: u8) -> u8 {
+        self.wiring[index as usize]
+    }
+
+    pub fn try_convert(&self, letter: char) -> Result<char, EnigmaError> {
+        let index = try_alpha_index(letter)? as u8;
+        Ok(ALPHA[self.swap(index) as usize])
+    }
+
     pub fn convert(&self, letter: char) -> char {
-        self.conversions[&letter]
+        match self.try_convert(letter) {
+            Ok(c) => c,
+            Err(e) => panic!("{}", e)
+        }
+    }
+
+    pub fn try_convert_inv(&self, letter: char) -> Result<char, EnigmaError> {
+        self.try_convert(letter)
     }
 
     pub fn convert_inv(&self, letter: char) -> char {
-        for (key, value) in &self.conversions {
-            let output = &letter;
-            if value == output {
-                return key.clone();
-            }
+        match self.try_convert_inv(letter) {
+            Ok(c) => c,
+            Err(e) => panic!("{}", e)
+        }
+    }
+
+    pub fn is_plugged(&self, letter: char) -> bool {
+        self.convert(letter) != letter
+    }
+
+    pub fn pairs(&self) -> Vec<(char, char)> {
+        ALPHA.iter()
+            .map(|&x| (x, self.convert(x)))
+            .filter(|(a, b)| a < b)
+            .collect()
+    }
+
+    pub fn try_connect(&mut self, letter_1: char, letter_2: char) -> Result<(), EnigmaError> {
+        let letter_1 = letter_1.to_ascii_uppercase();
+        let letter_2 = letter_2.to_ascii_uppercase();
+
+        let index_1 = try_alpha_index(letter_1)?;
+        let index_2 = try_alpha_index(letter_2)?;
+
+        // With every cable in use every letter is plugged, so this is checked first
+        if self.pairs().len() >= MAX_PAIRS {
+            return Err(EnigmaError::PlugboardFull);
+        }
+
+        if letter_1 == letter_2 || self.is_plugged(letter_1) || self.is_plugged(letter_2) {
+            return Err(EnigmaError::InvalidPlugboardPair(letter_1, letter_2));
         }
 
-        panic!("Could find inverse conversion of character '{}'", letter);
+        self.wiring[index_1] = index_2 as u8;
+        self.wiring[index_2] = index_1 as u8;
+
+        Ok(())
     }
 
-    fn _get_mutable_conversion_for_key(&mut self, letter: &char) -> &mut char {
-        match self.conversions.get_mut(letter) {
-            Some(c) => return c,
-            None => panic!("Failed to retrieve conversion for letter '{}'", letter)
+    pub fn swap_letter_wiring(&mut self, letter_1: char, letter_2: char) {
+        if let Err(e) = self.try_connect(letter_1, letter_2) {
+            panic!("{}", e);
         }
     }
 
-    pub fn swap_letter_wiring(mut self, letter_1: char, letter_2: char) {
-        let init_1: char = self.convert(letter_1);
-        let init_2: char = self.convert(letter_2);
-        *self._get_mutable_conversion_for_key(&letter_1) = letter_2;
-        *self._get_mutable_conversion_for_key(&letter_2) = letter_1;
-        *self._get_mutable_conversion_for_key(&init_2) = init_1;
-        *self._get_mutable_conversion_for_key(&init_1) = init_2;
+    // Removes the cable plugged into the given letter, returning the letter at the other end
+    pub fn try_disconnect(&mut self, letter: char) -> Result<Option<char>, EnigmaError> {
+        let letter = letter.to_ascii_uppercase();
+        let partner = self.try_convert(letter)?;
+
+        if partner == letter {
+            return Ok(None);
+        }
+
+        let index = alpha_index(letter);
+        let partner_index = alpha_index(partner);
+
+        self.wiring[index] = index as u8;
+        self.wiring[partner_index] = partner_index as u8;
+
+        Ok(Some(partner))
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::EnigmaError;
+
+    #[test]
+    fn test_plugboard_identity() {
+        let plugboard = super::Plugboard::new();
+        for letter in crate::rotor::ALPHA {
+            assert!(plugboard.convert(letter) == letter);
+        }
+        assert!(plugboard.pairs().is_empty());
+    }
+
+    #[test]
+    fn test_plugboard_conv_inv() {
+        let plugboard = super::Plugboard::from_pairs("AZ BP CM");
+        for letter in crate::rotor::ALPHA {
+            assert!(plugboard.convert_inv(plugboard.convert(letter)) == letter);
+        }
+    }
+
+    #[test]
+    fn test_plugboard_invalid_letter() {
+        let plugboard = super::Plugboard::new();
+        assert!(plugboard.try_convert('#') == Err(EnigmaError::InvalidLetter('#')));
+        assert!(plugboard.try_convert_inv('#') == Err(EnigmaError::InvalidLetter('#')));
+    }
+
+    #[test]
+    fn test_plugboard_pairs() {
+        let mut plugboard = super::Plugboard::from_pairs("AB cd EF GH");
+        assert!(plugboard.convert('A') == 'B');
+        assert!(plugboard.convert('D') == 'C');
+        assert!(plugboard.convert('Z') == 'Z');
+        assert!(plugboard.pairs() == vec![('A', 'B'), ('C', 'D'), ('E', 'F'), ('G', 'H')]);
+        assert!(plugboard.to_string() == "AB CD EF GH");
+
+        assert!(plugboard.try_disconnect('D') == Ok(Some('C')));
+        assert!(plugboard.try_disconnect('D') == Ok(None));
+        assert!(plugboard.convert('C') == 'C');
+        assert!(plugboard.to_string() == "AB EF GH");
+    }
+
+    #[test]
+    fn test_plugboard_invalid_pairs() {
+        assert!(super::Plugboard::try_from_pairs("AA").err() == Some(EnigmaError::InvalidPlugboardPair('A', 'A')));
+        assert!(super::Plugboard::try_from_pairs("AB BC").err() == Some(EnigmaError::InvalidPlugboardPair('B', 'C')));
+        assert!(super::Plugboard::try_from_pairs("ABC").err()
+            == Some(EnigmaError::MalformedPlugboardPair("ABC".to_string())));
+        assert!(super::Plugboard::try_from_pairs("A1").err() == Some(EnigmaError::InvalidLetter('1')));
+
+        let mut plugboard = super::Plugboard::from_pairs("AB CD EF GH IJ KL MN OP QR ST UV WX YZ");
+        assert!(plugboard.pairs().len() == super::MAX_PAIRS);
+        assert!(plugboard.try_connect('A', 'C') == Err(EnigmaError::PlugboardFull));
+        plugboard.try_disconnect('Y').unwrap();
+        assert!(plugboard.try_connect('Y', 'Z').is_ok());
     }
 }
@@ -1,73 +1,117 @@
-use std::collections::HashMap;
+use crate::error::EnigmaError;
+use crate::rotor::{try_alpha_index, wiring_table, ALPHA};
 
 pub struct Reflector {
-    conversions: HashMap<char, char>,
-    name: char
+    wiring: [u8; 26],
+    name: char,
+    thin: bool
 }
 
 impl Clone for Reflector {
     fn clone(&self) -> Self {
         Self {
-            conversions: self.conversions.clone(),
-            name: self.name.clone()
+            wiring: self.wiring,
+            name: self.name,
+            thin: self.thin
         }
     }
 }
 
+#[allow(non_snake_case)]
 impl Reflector {
-    pub fn convert(&self, letter: char) -> char {
-        self.conversions[&letter]
+    pub fn new(name: char, wiring: &str, thin: bool) -> Self {
+        Self {
+            wiring: wiring_table(wiring),
+            name,
+            thin
+        }
     }
 
-    pub fn Reflector_B() -> Self {
-        let conversions: HashMap<char, char> = [
-            ('A', 'Y'), ('B', 'R'), ('C', 'U'),
-			('D', 'H'), ('E', 'Q'), ('F', 'S'),
-			('G', 'L'), ('H', 'D'), ('K', 'N'),
-			('L', 'G'), ('M', 'O'), ('N', 'K'),
-			('O', 'M'), ('P', 'I'), ('Q', 'E'),
-			('R', 'B'), ('S', 'F'), ('T', 'Z'),
-			('U', 'C'), ('V', 'W'), ('W', 'V'),
-			('X', 'J'), ('Y', 'A'), ('Z', 'T'),
-			('I', 'P'), ('J', 'X')
-        ].iter().cloned().collect();
+    pub fn get_name(&self) -> char {
+        self.name
+    }
 
-        Self {
-            name: 'B',
-            conversions: conversions
+    pub fn is_thin(&self) -> bool {
+        self.thin
+    }
+
+    #[inline]
+    pub fn reflect(&self, index: u8) -> u8 {
+        self.wiring[index as usize]
+    }
+
+    pub fn try_convert(&self, letter: char) -> Result<char, EnigmaError> {
+        let index = try_alpha_index(letter)? as u8;
+        Ok(ALPHA[self.reflect(index) as usize])
+    }
+
+    pub fn convert(&self, letter: char) -> char {
+        match self.try_convert(letter) {
+            Ok(c) => c,
+            Err(e) => panic!("{}", e)
         }
     }
 
+    pub fn Reflector_A() -> Self {
+        Self::new('A', "EJMZALYXVBWFCRQUONTSPIKHGD", false)
+    }
+
+    pub fn Reflector_B() -> Self {
+        Self::new('B', "YRUHQSLDPXNGOKMIEBFZCWVJAT", false)
+    }
+
     pub fn Reflector_C() -> Self {
-        let conversions: HashMap<char, char> = [
-            ('A', 'F'), ('B', 'V'), ('C', 'P'),
-            ('D', 'J'), ('E', 'I'), ('F', 'A'),
-            ('G', 'O'), ('H', 'Y'), ('K', 'R'),
-            ('L', 'Z'), ('M', 'X'), ('N', 'W'),
-            ('O', 'G'), ('P', 'C'), ('Q', 'T'),
-            ('R', 'K'), ('S', 'U'), ('T', 'Q'),
-            ('U', 'S'), ('V', 'B'), ('W', 'N'),
-            ('X', 'M'), ('Y', 'H'), ('Z', 'L'),
-            ('I', 'E'), ('J', 'D')
-        ].iter().cloned().collect();
+        Self::new('C', "FVPJIAOYEDRZXWGCTKUQSBNMHL", false)
+    }
 
-        Self {
-            name: 'C',
-            conversions: conversions
-        }
+    // Thin reflectors of the M4, used alongside the Greek wheels
+    pub fn Reflector_B_Thin() -> Self {
+        Self::new('B', "ENKQAUYWJICOPBLMDXZVFTHRGS", true)
+    }
+
+    pub fn Reflector_C_Thin() -> Self {
+        Self::new('C', "RDOBJNTKVEHMLFCWZAXGYIPSUQ", true)
     }
 }
 
-pub fn Reflectors(reflector_type: char) -> Reflector {
+pub fn try_reflectors(reflector_type: char) -> Result<Reflector, EnigmaError> {
     match reflector_type {
-        'B' => Reflector::Reflector_B(),
-        'C' => Reflector::Reflector_C(),
-        _ => panic!("Unrecognised reflector type '{}'", reflector_type)
+        'A' => Ok(Reflector::Reflector_A()),
+        'B' => Ok(Reflector::Reflector_B()),
+        'C' => Ok(Reflector::Reflector_C()),
+        _ => Err(EnigmaError::UnknownReflector(reflector_type))
+    }
+}
+
+pub fn try_thin_reflectors(reflector_type: char) -> Result<Reflector, EnigmaError> {
+    match reflector_type {
+        'B' => Ok(Reflector::Reflector_B_Thin()),
+        'C' => Ok(Reflector::Reflector_C_Thin()),
+        _ => Err(EnigmaError::UnknownReflector(reflector_type))
+    }
+}
+
+#[allow(non_snake_case)]
+pub fn Reflectors(reflector_type: char) -> Reflector {
+    match try_reflectors(reflector_type) {
+        Ok(r) => r,
+        Err(e) => panic!("{}", e)
     }
 }
 
 #[cfg(test)]
+#[allow(non_snake_case)]
 mod tests {
+    #[test]
+    fn test_reflector_A_conv() {
+        let reflector = super::Reflector::Reflector_A();
+
+        for letter in crate::rotor::ALPHA {
+            assert!(reflector.convert(reflector.convert(letter)) == letter);
+        }
+        assert!(reflector.convert('X') == 'H');
+    }
+
     #[test]
     fn test_reflector_B_conv() {
         let in_char = 'X';
@@ -91,4 +135,32 @@ mod tests {
 
         assert!(ref_out == out_char);
     }
+
+    #[test]
+    fn test_thin_reflectors() {
+        for name in ['B', 'C'] {
+            let reflector = super::try_thin_reflectors(name).unwrap();
+            assert!(reflector.is_thin() && reflector.get_name() == name);
+
+            for letter in crate::rotor::ALPHA {
+                assert!(reflector.convert(letter) != letter);
+                assert!(reflector.convert(reflector.convert(letter)) == letter);
+            }
+        }
+        assert!(super::Reflector::Reflector_B_Thin().convert('A') == 'E');
+        assert!(super::Reflector::Reflector_C_Thin().convert('A') == 'R');
+        assert!(super::try_thin_reflectors('A').is_err());
+    }
+
+    #[test]
+    fn test_unknown_reflector() {
+        assert!(super::try_reflectors('X').is_err());
+        assert!(super::try_reflectors('B').is_ok());
+    }
+
+    #[test]
+    fn test_reflector_invalid_letter() {
+        let reflector = super::Reflector::Reflector_B();
+        assert!(reflector.try_convert('1') == Err(crate::error::EnigmaError::InvalidLetter('1')));
+    }
 }
@@ -0,0 +1,273 @@
+use std::io::{BufRead, Write};
+use enigma::{Enigma, MachineConfig};
+
+pub const HELP: &str = "Type text to encode it, the machine keeps its position between lines.
+
+Commands:
+    :pos             Show the rotor positions
+    :set <KEY>       Move the rotors to a new position, e.g. :set BLA
+    :reset           Return the machine to its starting configuration
+    :plug [PAIRS]    Connect plugboard pairs, e.g. :plug AB CD, or list them
+    :unplug <LETTER> Remove the cable plugged into a letter
+    :rotors          Show the machine type, rotor order and reflector
+    :save <FILE>     Write the machine setup at its current position to a file
+    :load <FILE>     Replace the machine with one read from a configuration file
+    :history         Show the lines typed and the changes made so far
+    :undo            Revert the machine to before the last line or change
+    :help            Print this message
+    :quit            Leave the session";
+
+// A typed line, or a command changing the machine, with the machine as it
+// stood before so that it can be undone
+struct Entry {
+    input: String,
+    output: String,
+    before: Enigma
+}
+
+pub enum Reply {
+    Text(String),
+    Quit
+}
+
+pub struct Session {
+    machine: Enigma,
+    initial: Enigma,
+    history: Vec<Entry>
+}
+
+impl Session {
+    pub fn new(machine: Enigma) -> Self {
+        Self {
+            initial: machine.clone(),
+            machine,
+            history: Vec::new()
+        }
+    }
+
+    pub fn position(&self) -> String {
+        self.machine.get_key()
+    }
+
+    fn type_line(&mut self, line: &str) -> Result<String, String> {
+        // Typed on a copy so that a bad character leaves the machine untouched
+        let mut machine = self.machine.clone();
+        let output = line.chars()
+            .filter(|x| !x.is_whitespace())
+            .map(|x| machine.try_type_letter(x))
+            .collect::<Result<String, _>>()
+            .map_err(|e| e.to_string())?;
+
+        let before = std::mem::replace(&mut self.machine, machine);
+        self.history.push(Entry { input: line.to_string(), output: output.clone(), before });
+
+        Ok(format!("{}  [{}]", output, self.position()))
+    }
+
+    fn record(&mut self, command: &str, args: &str, output: &str, before: Enigma) {
+        self.history.push(Entry { input: format!("{} {}", command, args), output: output.to_string(), before });
+    }
+
+    fn command(&mut self, command: &str, args: &str) -> Result<Reply, String> {
+        let text = match command {
+            ":pos" => self.position(),
+            ":set" => {
+                let before = self.machine.clone();
+                self.machine.try_set_key(args.to_string()).map_err(|e| e.to_string())?;
+                self.record(command, args, &self.position(), before);
+                format!("Position set to {}", self.position())
+            },
+            ":reset" => {
+                self.machine = self.initial.clone();
+                self.history.clear();
+                format!("Machine reset to {}", self.position())
+            },
+            ":plug" => {
+                let mut machine = self.machine.clone();
+                for pair in args.split_whitespace() {
+                    let letters: Vec<char> = pair.chars().collect();
+                    if letters.len() != 2 {
+                        return Err(format!("Plugboard pair '{}' must consist of exactly two letters", pair));
+                    }
+                    machine.try_rewire_plugboard(letters[0], letters[1]).map_err(|e| e.to_string())?;
+                }
+                let before = std::mem::replace(&mut self.machine, machine);
+                let pairs: Vec<String> = self.machine.plugboard_pairs().iter()
+                    .map(|(a, b)| format!("{}{}", a, b))
+                    .collect();
+                if !args.is_empty() {
+                    self.record(command, args, &pairs.join(" "), before);
+                }
+                format!("Plugboard: {}", pairs.join(" "))
+            },
+            ":unplug" => {
+                let letter = match args.chars().next() {
+                    Some(l) => l,
+                    None => return Err("Expected a letter to unplug".to_string())
+                };
+                let before = self.machine.clone();
+                match self.machine.try_unplug(letter).map_err(|e| e.to_string())? {
+                    Some(partner) => {
+                        let output = format!("Removed cable {}{}", letter.to_ascii_uppercase(), partner);
+                        self.record(command, args, &output, before);
+                        output
+                    },
+                    None => format!("No cable plugged into {}", letter.to_ascii_uppercase())
+                }
+            },
+            ":rotors" => format!(
+                "{}: rotors {}, reflector {}",
+                self.machine.get_type(),
+                self.machine.get_rotor_names().join(" "),
+                self.machine.get_reflector_name()
+            ),
+            ":save" => {
+                if args.is_empty() {
+                    return Err("Expected a file to save to".to_string());
+                }
+                std::fs::write(args, self.machine.to_config().to_text())
+                    .map_err(|e| format!("Failed to write '{}': {}", args, e))?;
+                format!("Saved machine at {} to {}", self.position(), args)
+            },
+            ":load" => {
+                let text = std::fs::read_to_string(args)
+                    .map_err(|e| format!("Failed to read '{}': {}", args, e))?;
+                let config = MachineConfig::try_parse(&text).map_err(|e| e.to_string())?;
+                *self = Self::new(Enigma::try_from_config(&config).map_err(|e| e.to_string())?);
+                format!("Loaded machine at {} from {}", self.position(), args)
+            },
+            ":history" => self.history.iter().enumerate()
+                .map(|(i, x)| format!("{}: {} -> {}", i + 1, x.input, x.output))
+                .collect::<Vec<String>>()
+                .join("\n"),
+            ":undo" => match self.history.pop() {
+                Some(entry) => {
+                    self.machine = entry.before;
+                    format!("Undid '{}', position {}", entry.input, self.position())
+                },
+                None => return Err("Nothing to undo".to_string())
+            },
+            ":help" => HELP.to_string(),
+            ":quit" | ":q" => return Ok(Reply::Quit),
+            _ => return Err(format!("Unrecognised command '{}', type :help for a list", command))
+        };
+
+        Ok(Reply::Text(text))
+    }
+
+    pub fn execute(&mut self, line: &str) -> Result<Reply, String> {
+        let line = line.trim();
+
+        if line.starts_with(':') {
+            let (command, args) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            return self.command(command, args.trim());
+        }
+
+        self.type_line(line).map(Reply::Text)
+    }
+}
+
+pub fn run(machine: Enigma) -> Result<(), String> {
+    let mut session = Session::new(machine);
+    let stdin = std::io::stdin();
+    let mut lines = stdin.lock().lines();
+
+    println!("Enigma {} ready, type :help for commands.", session.machine.get_type());
+
+    loop {
+        print!("[{}]> ", session.position());
+        std::io::stdout().flush().map_err(|e| e.to_string())?;
+
+        let line = match lines.next() {
+            Some(l) => l.map_err(|e| format!("Failed to read user input, with error '{}'", e))?,
+            None => return Ok(())
+        };
+
+        match session.execute(&line) {
+            Ok(Reply::Text(text)) => if !text.is_empty() {println!("{}", text)},
+            Ok(Reply::Quit) => return Ok(()),
+            Err(e) => println!("error: {}", e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Reply, Session};
+    use enigma::Enigma;
+
+    fn text(reply: Result<Reply, String>) -> String {
+        match reply {
+            Ok(Reply::Text(t)) => t,
+            Ok(Reply::Quit) => "QUIT".to_string(),
+            Err(e) => panic!("{}", e)
+        }
+    }
+
+    #[test]
+    fn test_session_continues_between_lines() {
+        let mut session = Session::new(Enigma::new(vec![1, 2, 3], 'B', "M3".to_string()));
+
+        assert!(text(session.execute("AAAAA")) == "BDZGO  [AAF]");
+        assert!(text(session.execute("AAAAA")) == "WCXLT  [AAK]");
+        assert!(text(session.execute(":history")) == "1: AAAAA -> BDZGO\n2: AAAAA -> WCXLT");
+
+        text(session.execute(":undo"));
+        assert!(session.position() == "AAF");
+        assert!(text(session.execute("aaa aa")) == "WCXLT  [AAK]");
+
+        // A :set is undone on its own, leaving the line before it in place
+        text(session.execute(":set XYZ"));
+        assert!(text(session.execute(":history")).ends_with("3: :set XYZ -> XYZ"));
+        assert!(text(session.execute(":undo")) == "Undid ':set XYZ', position AAK");
+        assert!(text(session.execute(":history")) == "1: AAAAA -> BDZGO\n2: aaa aa -> WCXLT");
+
+        text(session.execute(":reset"));
+        assert!(session.position() == "AAA");
+        assert!(text(session.execute(":history")).is_empty());
+    }
+
+    #[test]
+    fn test_session_commands() {
+        let mut session = Session::new(Enigma::new(vec![2, 4, 5], 'B', "M3".to_string()));
+
+        assert!(text(session.execute(":set BLA")) == "Position set to BLA");
+        assert!(text(session.execute(":plug AV BS")) == "Plugboard: AV BS");
+        assert!(session.execute(":plug AC").is_err());
+        assert!(text(session.execute(":plug")) == "Plugboard: AV BS");
+        assert!(text(session.execute(":unplug s")) == "Removed cable SB");
+        assert!(text(session.execute(":rotors")) == "M3: rotors II IV V, reflector B");
+        assert!(text(session.execute(":pos")) == "BLA");
+        assert!(session.execute("HELLO 1").is_err());
+        assert!(session.position() == "BLA");
+
+        // Changes to the machine are undone in turn, the failed :plug AC among none of them
+        assert!(text(session.execute(":undo")) == "Undid ':unplug s', position BLA");
+        assert!(text(session.execute(":plug")) == "Plugboard: AV BS");
+        assert!(text(session.execute(":undo")) == "Undid ':plug AV BS', position BLA");
+        assert!(text(session.execute(":plug")) == "Plugboard: ");
+        assert!(text(session.execute(":undo")) == "Undid ':set BLA', position AAA");
+        assert!(session.execute(":undo").is_err());
+        assert!(session.execute(":frobnicate").is_err());
+        assert!(text(session.execute(":quit")) == "QUIT");
+    }
+
+    #[test]
+    fn test_session_save_load() {
+        let path = std::env::temp_dir().join(format!("enigma-repl-save-{}.txt", std::process::id()));
+        let path = path.display().to_string();
+
+        let mut session = Session::new(Enigma::new(vec![2, 4, 5], 'B', "M3".to_string()));
+        text(session.execute(":set BLA"));
+        text(session.execute(":plug AV BS"));
+        assert!(text(session.execute(&format!(":save {}", path))) == format!("Saved machine at BLA to {}", path));
+
+        let mut restored = Session::new(Enigma::new(vec![1, 2, 3], 'B', "M3".to_string()));
+        assert!(text(restored.execute(&format!(":load {}", path))) == format!("Loaded machine at BLA from {}", path));
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(text(restored.execute(":rotors")) == "M3: rotors II IV V, reflector B");
+        assert!(text(restored.execute("HELLO")) == text(session.execute("HELLO")));
+        assert!(restored.execute(":load").is_err());
+    }
+}
@@ -1,4 +1,7 @@
-use std::collections::HashMap;
+use crate::error::EnigmaError;
+
+pub const BETA: i32 = 9;
+pub const GAMMA: i32 = 10;
 
 pub const ALPHA: [char; 26] = [
     'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I',
@@ -6,20 +9,53 @@ pub const ALPHA: [char; 26] = [
     'S', 'T', 'U', 'V', 'W', 'X', 'Y', 'Z'
 ];
 
+pub fn try_alpha_index(letter: char) -> Result<usize, EnigmaError> {
+    if letter.is_ascii_uppercase() {
+        Ok((letter as u8 - b'A') as usize)
+    }
+    else {
+        Err(EnigmaError::InvalidLetter(letter))
+    }
+}
+
 pub fn alpha_index(letter: char) -> usize {
-    let index = ALPHA.iter().position(|&x| x == letter);
+    match try_alpha_index(letter) {
+        Ok(x) => x,
+        Err(e) => panic!("{}", e)
+    }
+}
 
-    match index {
-        Some(x) => x,
-        None => panic!("Cannot get index of invalid alpha character {}", letter)
+// Builds a permutation table from the letters wired to A, B, C, ... in turn
+pub(crate) fn wiring_table(wiring: &str) -> [u8; 26] {
+    let mut table = [0u8; 26];
+    for (i, letter) in wiring.bytes().enumerate() {
+        table[i] = letter - b'A';
     }
+    table
+}
+
+// Reduces an index in 0..52 back onto the alphabet, cheaper than a modulo
+#[inline]
+fn wrap(index: u8) -> u8 {
+    if index >= 26 {index - 26} else {index}
+}
+
+pub(crate) fn invert_table(table: &[u8; 26]) -> [u8; 26] {
+    let mut inverse = [0u8; 26];
+    for (i, &x) in table.iter().enumerate() {
+        inverse[x as usize] = i as u8;
+    }
+    inverse
 }
 
 #[derive(Debug)]
 pub struct Rotor {
     notches: Vec<char>,
-    wiring: HashMap<i32, i32>,
-    face: char,
+    notch_mask: u32,
+    wiring: [u8; 26],
+    inverse: [u8; 26],
+    position: u8,
+    ring: u8,
     name: String
 }
 
@@ -27,7 +63,8 @@ impl PartialEq for Rotor {
     fn eq(&self, other: &Self) -> bool {
         (self.notches == other.notches) &&
         (self.wiring == other.wiring) &&
-        (self.face == other.face) &&
+        (self.position == other.position) &&
+        (self.ring == other.ring) &&
         (self.name == other.name)
     }
 }
@@ -36,289 +73,381 @@ impl Clone for Rotor {
     fn clone(&self) -> Self {
         Self {
             notches: self.notches.clone(),
-            wiring: self.wiring.clone(),
-            face: self.face.clone(),
+            notch_mask: self.notch_mask,
+            wiring: self.wiring,
+            inverse: self.inverse,
+            position: self.position,
+            ring: self.ring,
             name: self.name.clone()
         }
     }
 }
 
+#[allow(non_snake_case)]
 impl Rotor {
+    pub fn new(name: &str, wiring: &str, notches: &[char]) -> Self {
+        let wiring = wiring_table(wiring);
+
+        Self {
+            name: name.to_string(),
+            notches: notches.to_vec(),
+            notch_mask: notches.iter().fold(0, |mask, &x| mask | (1 << alpha_index(x))),
+            inverse: invert_table(&wiring),
+            position: 0,
+            ring: 0,
+            wiring
+        }
+    }
+
     pub fn rotate(&mut self, other: Option<Rotor>) {
-        let mut pos: usize = alpha_index(self.face);
-        pos = if pos > 24 {0} else {pos+1};
-        self.face = ALPHA[pos];
+        self.position = if self.position == 25 {0} else {self.position + 1};
 
-        match other {
-            Some(mut other) => other.rotate(None),
-            None => ()
+        if let Some(mut other) = other {
+            other.rotate(None);
         }
     }
 
     pub fn get_face_letter(&self) -> char {
-        self.face.clone()
+        ALPHA[self.position as usize]
+    }
+
+    pub fn try_set_face_letter(&mut self, letter: char) -> Result<(), EnigmaError> {
+        self.position = try_alpha_index(letter)? as u8;
+        Ok(())
+    }
+
+    pub fn get_position(&self) -> u8 {
+        self.position
+    }
+
+    pub fn set_position(&mut self, position: u8) {
+        self.position = position % 26;
+    }
+
+    pub fn get_name(&self) -> String {
+        self.name.clone()
+    }
+
+    pub fn is_greek(&self) -> bool {
+        self.name == "Beta" || self.name == "Gamma"
     }
 
     pub fn get_notches(&self) -> Vec<char> {
         self.notches.clone()
     }
 
-    fn _get_mutable_wiring_terminal(&mut self, terminal: &i32) -> &mut i32 {
-        match self.wiring.get_mut(terminal) {
-            Some(t) => return t,
-            None => panic!("Failed to retrieve terminal {}", terminal)
-        }
+    // Notches are given as the letter showing in the window when the rotor
+    // carries its left-hand neighbour over on the next key press.
+    #[inline]
+    pub fn is_at_notch(&self) -> bool {
+        self.notch_mask & (1 << self.position) != 0
     }
 
     // RINGSTELLUNG
+    // The ring setting is the letter of the alphabet ring lying against the
+    // first contact of the wiring core, A (or 01) being the neutral setting.
+    // It is held apart from the position so that the two can be set in any
+    // order, and as the notch is fixed to the alphabet ring the turnover
+    // still happens at the same window letter whatever the ring setting.
+    pub fn get_ring_setting(&self) -> char {
+        ALPHA[self.ring as usize]
+    }
+
+    pub fn try_set_ring_setting(&mut self, letter: char) -> Result<(), EnigmaError> {
+        self.ring = try_alpha_index(letter)? as u8;
+        Ok(())
+    }
+
+    pub fn get_ring_offset(&self) -> u8 {
+        self.ring
+    }
+
+    pub fn set_ring_offset(&mut self, ring: u8) {
+        self.ring = ring % 26;
+    }
+
     pub fn rotate_inner_ring(&mut self) {
-        let x: i32 = self.wiring[&0];
-        for i in 0..self.wiring.keys().len() {
-            if i == 25 {
-                *self._get_mutable_wiring_terminal(&(i as i32)) = x;
-            }
-            else {
-                *self._get_mutable_wiring_terminal(&(i as i32)) = self.wiring[&(i as i32+1)];
-            }
-        }
+        self.ring = if self.ring == 25 {0} else {self.ring + 1};
     }
 
     pub fn get_output_terminal(self, letter: char) -> i32 {
-        let pos: usize = alpha_index(letter);
-        self.wiring[&(pos as i32)]
+        self.wiring[alpha_index(letter)] as i32
     }
 
     pub fn get_input_terminal(self, letter: char) -> i32 {
-        let pos: usize = alpha_index(letter);
-        for i in 0..self.wiring.keys().len() {
-            if i == pos {
-                return i as i32;
-            }
-        }
-        panic!("Could not find Input terminal for letter {}", letter);
+        alpha_index(letter) as i32
     }
 
-    pub fn convert(&self, letter: char) -> char {
-        let pos: usize = alpha_index(letter);
-        ALPHA[self.wiring[&(pos as i32)] as usize]
+    // Conversions are made relative to the offset of the wiring core against
+    // the fixed entry contacts, being the position less the ring setting, and
+    // the offset is applied on both entry to and exit from the wheel.
+    #[inline]
+    fn offset(&self) -> u8 {
+        wrap(self.position + 26 - self.ring)
     }
 
-    pub fn convert_inv(&self, letter: char) -> char {
-        let pos: usize = alpha_index(letter);
-
-        for i in 0..self.wiring.keys().len() {
-            if pos as i32 == self.wiring[&(i as i32)] {
-                return ALPHA[i as usize];
-            }
-        }
+    #[inline]
+    pub fn forward(&self, index: u8) -> u8 {
+        let offset = self.offset();
+        let contact = self.wiring[wrap(index + offset) as usize];
+        wrap(contact + 26 - offset)
+    }
 
-        panic!("Could not find the inverse of character '{}'", letter);
+    #[inline]
+    pub fn backward(&self, index: u8) -> u8 {
+        let offset = self.offset();
+        let contact = self.inverse[wrap(index + offset) as usize];
+        wrap(contact + 26 - offset)
     }
 
-    pub fn Rotor_1() -> Self {
-        let mut notches = Vec::new();
-        notches.push('R');
-        let wiring: HashMap<i32, i32> = [
-            (0, 4), (1, 10), (2, 12),
-			(3, 5), (4, 11), (5, 6),
-			(6, 3), (7, 16), (8, 21),
-			(9, 25), (10, 13), (11, 19),
-			(12, 14), (13, 22), (14, 24),
-			(15, 7), (16, 23), (17, 20),
-			(18, 18), (19, 15), (20, 0),
-			(21, 8), (22, 1), (23, 17),
-			(24, 2), (25, 9)
-        ].iter().cloned().collect();
+    pub fn try_convert(&self, letter: char) -> Result<char, EnigmaError> {
+        let index = try_alpha_index(letter)? as u8;
+        Ok(ALPHA[self.forward(index) as usize])
+    }
 
-        Self {
-            name: "I".to_string(),
-            notches: notches,
-            face: 'A',
-            wiring: wiring
+    pub fn convert(&self, letter: char) -> char {
+        match self.try_convert(letter) {
+            Ok(c) => c,
+            Err(e) => panic!("{}", e)
         }
     }
 
-    pub fn Rotor_2() -> Self {
-        let mut notches = Vec::new();
-        notches.push('F');
-        let wiring: HashMap<i32, i32> = [
-            (0, 0), (1, 9), (2, 3),
-			(3, 10), (4, 18), (5, 8),
-			(6, 17), (7, 20), (8, 23),
-			(9, 1), (10, 11), (11, 7),
-			(12, 22), (13, 19), (14, 12),
-			(15, 2), (16, 16), (17, 6),
-			(18, 25), (19, 13), (20, 15),
-			(21, 24), (22, 5), (23, 21),
-			(24, 14), (25, 4)
-        ].iter().cloned().collect();
+    pub fn try_convert_inv(&self, letter: char) -> Result<char, EnigmaError> {
+        let index = try_alpha_index(letter)? as u8;
+        Ok(ALPHA[self.backward(index) as usize])
+    }
 
-        Self {
-            name: "II".to_string(),
-            notches: notches,
-            face: 'A',
-            wiring: wiring
+    pub fn convert_inv(&self, letter: char) -> char {
+        match self.try_convert_inv(letter) {
+            Ok(c) => c,
+            Err(e) => panic!("{}", e)
         }
     }
 
-    pub fn Rotor_3() -> Self {
-        let mut notches = Vec::new();
-        notches.push('W');
-        let wiring: HashMap<i32, i32> = [
-            (0, 1), (1, 3), (2, 5),
-			(3, 7), (4, 9), (5, 11),
-			(6, 2), (7, 15), (8, 17),
-			(9, 19), (10, 23), (11, 21),
-			(12, 25), (13, 13), (14, 24),
-			(15, 4), (16, 8), (17, 22),
-			(18, 6), (19, 0), (20, 10),
-			(21, 12), (22, 20), (23, 18),
-			(24, 16), (25, 14)
-        ].iter().cloned().collect();
+    pub fn Rotor_1() -> Self {
+        Self::new("I", "EKMFLGDQVZNTOWYHXUSPAIBRCJ", &['Q'])
+    }
 
-        Self {
-            name: "III".to_string(),
-            notches: notches,
-            face: 'A',
-            wiring: wiring
-        }
+    pub fn Rotor_2() -> Self {
+        Self::new("II", "AJDKSIRUXBLHWTMCQGZNPYFVOE", &['E'])
     }
 
-    pub fn Rotor_4() -> Self {
-        let mut notches = Vec::new();
-        notches.push('K');
-        let wiring: HashMap<i32, i32> = [
-            (0, 4), (1, 18), (2, 14),
-			(3, 21), (4, 15), (5, 25),
-			(6, 9), (7, 0), (8, 24),
-			(9, 16), (10, 20), (11, 8),
-			(12, 17), (13, 7), (14, 23),
-			(15, 11), (16, 13), (17, 5),
-			(18, 19), (19, 6), (20, 10),
-			(21, 3), (22, 2), (23, 12),
-			(24, 22), (25, 1)
-        ].iter().cloned().collect();
+    pub fn Rotor_3() -> Self {
+        Self::new("III", "BDFHJLCPRTXVZNYEIWGAKMUSQO", &['V'])
+    }
 
-        Self {
-            name: "IV".to_string(),
-            notches: notches,
-            face: 'A',
-            wiring: wiring
-        }
+    pub fn Rotor_4() -> Self {
+        Self::new("IV", "ESOVPZJAYQUIRHXLNFTGKDCMWB", &['J'])
     }
 
     pub fn Rotor_5() -> Self {
-        let mut notches = Vec::new();
-        notches.push('A');
-        let wiring: HashMap<i32, i32> = [
-            (0, 21), (1, 25), (2, 1),
-            (3, 17), (4, 6), (5, 8),
-            (6, 19), (7, 24), (8, 20),
-            (9, 15), (10, 18), (11, 3),
-            (12, 13), (13, 7), (14, 11),
-            (15, 23), (16, 0), (17, 22),
-            (18, 12), (19, 9), (20, 16),
-            (21, 14), (22, 5), (23, 4),
-            (24, 2), (25, 10)
-        ].iter().cloned().collect();
-
-        Self {
-            name: "V".to_string(),
-            notches: notches,
-            face: 'A',
-            wiring: wiring
-        }
+        Self::new("V", "VZBRGITYUPSDNHLXAWMJQOFECK", &['Z'])
     }
 
     pub fn Rotor_6() -> Self {
-        let mut notches = Vec::new();
-        notches.push('A');
-        notches.push('N');
-        let wiring: HashMap<i32, i32> = [
-            (0, 9), (1, 15), (2, 6),
-            (3, 21), (4, 14), (5, 20),
-            (6, 12), (7, 5), (8, 24),
-            (9, 16), (10, 1), (11, 4),
-            (12, 13), (13, 7), (14, 25),
-            (15, 17), (16, 3), (17, 10),
-            (18, 0), (19, 18), (20, 23),
-            (21, 11), (22, 8), (23, 2),
-            (24, 19), (25, 22)
-        ].iter().cloned().collect();
-
-        Self {
-            name: "VI".to_string(),
-            notches: notches,
-            face: 'A',
-            wiring: wiring
-        }
+        Self::new("VI", "JPGVOUMFYQBENHZRDKASXLICTW", &['Z', 'M'])
     }
 
     pub fn Rotor_7() -> Self {
-        let mut notches = Vec::new();
-        notches.push('A');
-        notches.push('N');
-        let wiring: HashMap<i32, i32> = [
-            (0, 13), (1, 25), (2, 9),
-            (3, 7), (4, 6), (5, 17),
-            (6, 2), (7, 23), (8, 12),
-            (9, 24), (10, 18), (11, 22),
-            (12, 1), (13, 14), (14, 20),
-            (15, 5), (16, 0), (17, 8),
-            (18, 21), (19, 11), (20, 15),
-            (21, 4), (22, 10), (23, 16),
-            (24, 3), (25, 19)
-        ].iter().cloned().collect();
-
-        Self {
-            name: "VII".to_string(),
-            notches: notches,
-            face: 'A',
-            wiring: wiring
-        }
+        Self::new("VII", "NZJHGRCXMYSWBOUFAIVLPEKQDT", &['Z', 'M'])
     }
 
     pub fn Rotor_8() -> Self {
-        let mut notches = Vec::new();
-        notches.push('A');
-        notches.push('N');
-        let wiring: HashMap<i32, i32> = [
-            (0, 5), (1, 10), (2, 16),
-            (3, 7), (4, 19), (5, 11),
-            (6, 23), (7, 14), (8, 2),
-            (9, 1), (10, 9), (11, 18),
-            (12, 15), (13, 3), (14, 25),
-            (15, 17), (16, 0), (17, 12),
-            (18, 4), (19, 22), (20, 13),
-            (21, 8), (22, 20), (23, 24),
-            (24, 6), (25, 21)
-        ].iter().cloned().collect();
+        Self::new("VIII", "FKQHTLXOCBJSPDZRAMEWNIUYGV", &['Z', 'M'])
+    }
 
-        Self {
-            name: "VIII".to_string(),
-            notches: notches,
-            face: 'A',
-            wiring: wiring
-        }
+    // Greek wheels (Zusatzwalzen), fitted only in the fourth position of the M4
+    pub fn Rotor_Beta() -> Self {
+        Self::new("Beta", "LEYJVCNIXWPBQMDRTAKZGFUHOS", &[])
+    }
+
+    pub fn Rotor_Gamma() -> Self {
+        Self::new("Gamma", "FSOKANUERHMBTIYCWLQPZXVGJD", &[])
     }
 }
 
-pub fn Rotors(rotor_type: i32) -> Rotor {
+// Advances the rotors, given from left to right, as the pawls would on a
+// key press. Only the three right-most rotors are driven, so the fourth
+// wheel of an M4 never moves. A middle rotor sitting at its notch is caught
+// by the pawl of the left rotor and so steps along with it, giving the
+// double-step anomaly.
+#[inline]
+pub(crate) fn step_rotors(rotors: &mut [Rotor]) {
+    let n_rotors = rotors.len();
+
+    if rotors[n_rotors - 2].is_at_notch() {
+        rotors[n_rotors - 3].rotate(None);
+        rotors[n_rotors - 2].rotate(None);
+    }
+    else if rotors[n_rotors - 1].is_at_notch() {
+        rotors[n_rotors - 2].rotate(None);
+    }
+
+    rotors[n_rotors - 1].rotate(None);
+}
+
+// Ring settings are given either as letters, "BUL", or as the numbers
+// found on the key sheets, "02 21 12" or "02,21,12", and returned as the
+// offset of each ring from the neutral A (01) setting.
+pub fn try_parse_ring_settings(settings: &str) -> Result<Vec<u8>, EnigmaError> {
+    let settings = settings.trim();
+
+    if settings.chars().all(|x| x.is_ascii_alphabetic()) {
+        return settings.to_ascii_uppercase().chars()
+            .map(|x| try_alpha_index(x).map(|i| i as u8))
+            .collect();
+    }
+
+    settings.split([',', ' '])
+        .filter(|x| !x.is_empty())
+        .map(|x| match x.parse::<u8>() {
+            Ok(n) if (1..=26).contains(&n) => Ok(n - 1),
+            _ => Err(EnigmaError::InvalidRingSetting(x.to_string()))
+        })
+        .collect()
+}
+
+const ROTOR_NAMES: [(&str, i32); 10] = [
+    ("I", 1), ("II", 2), ("III", 3), ("IV", 4), ("V", 5),
+    ("VI", 6), ("VII", 7), ("VIII", 8), ("BETA", BETA), ("GAMMA", GAMMA)
+];
+
+// Accepts either the numeric rotor id or its name, e.g. "4", "IV" or "Beta"
+pub fn try_rotor_id(name: &str) -> Result<i32, EnigmaError> {
+    let upper_name = name.trim().to_ascii_uppercase();
+
+    if let Ok(id) = upper_name.parse::<i32>() {
+        try_rotors(id)?;
+        return Ok(id);
+    }
+
+    match ROTOR_NAMES.iter().find(|(x, _)| *x == upper_name) {
+        Some((_, id)) => Ok(*id),
+        None => Err(EnigmaError::UnknownRotorName(name.to_string()))
+    }
+}
+
+pub fn try_rotors(rotor_type: i32) -> Result<Rotor, EnigmaError> {
     match rotor_type {
-        1 => Rotor::Rotor_1(),
-        2 => Rotor::Rotor_2(),
-        3 => Rotor::Rotor_3(),
-        4 => Rotor::Rotor_4(),
-        5 => Rotor::Rotor_5(),
-        6 => Rotor::Rotor_6(),
-        7 => Rotor::Rotor_7(),
-        8 => Rotor::Rotor_8(),
-        _ => panic!("No rotor for id {}", rotor_type)
+        1 => Ok(Rotor::Rotor_1()),
+        2 => Ok(Rotor::Rotor_2()),
+        3 => Ok(Rotor::Rotor_3()),
+        4 => Ok(Rotor::Rotor_4()),
+        5 => Ok(Rotor::Rotor_5()),
+        6 => Ok(Rotor::Rotor_6()),
+        7 => Ok(Rotor::Rotor_7()),
+        8 => Ok(Rotor::Rotor_8()),
+        BETA => Ok(Rotor::Rotor_Beta()),
+        GAMMA => Ok(Rotor::Rotor_Gamma()),
+        _ => Err(EnigmaError::UnknownRotor(rotor_type))
+    }
+}
+
+#[allow(non_snake_case)]
+pub fn Rotors(rotor_type: i32) -> Rotor {
+    match try_rotors(rotor_type) {
+        Ok(r) => r,
+        Err(e) => panic!("{}", e)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::error::EnigmaError;
+
+    #[test]
+    fn test_invalid_alpha_index() {
+        assert!(super::try_alpha_index('7') == Err(EnigmaError::InvalidLetter('7')));
+        assert!(super::try_alpha_index('a') == Err(EnigmaError::InvalidLetter('a')));
+        assert!(super::try_alpha_index('Z') == Ok(25));
+    }
+
+    #[test]
+    fn test_unknown_rotor() {
+        assert!(super::try_rotors(0) == Err(EnigmaError::UnknownRotor(0)));
+        assert!(super::try_rotors(42) == Err(EnigmaError::UnknownRotor(42)));
+        assert!(super::try_rotors(3).is_ok());
+    }
+
+    #[test]
+    fn test_rotor_invalid_letter() {
+        let rotor = super::Rotor::Rotor_1();
+        assert!(rotor.try_convert('?') == Err(EnigmaError::InvalidLetter('?')));
+        assert!(rotor.try_convert_inv('?') == Err(EnigmaError::InvalidLetter('?')));
+    }
+    #[test]
+    fn test_rotor_id() {
+        assert!(super::try_rotor_id("4") == Ok(4));
+        assert!(super::try_rotor_id("iv") == Ok(4));
+        assert!(super::try_rotor_id("VIII") == Ok(8));
+        assert!(super::try_rotor_id("Beta") == Ok(super::BETA));
+        assert!(super::try_rotor_id("gamma") == Ok(super::GAMMA));
+        assert!(super::try_rotor_id("12") == Err(EnigmaError::UnknownRotor(12)));
+        assert!(super::try_rotor_id("IX") == Err(EnigmaError::UnknownRotorName("IX".to_string())));
+    }
+
+    #[test]
+    fn test_rotor_turnover() {
+        let turnovers = [
+            (1, vec!['Q']), (2, vec!['E']), (3, vec!['V']), (4, vec!['J']),
+            (5, vec!['Z']), (6, vec!['Z', 'M']), (7, vec!['Z', 'M']), (8, vec!['Z', 'M'])
+        ];
+
+        for (rotor_id, notches) in turnovers {
+            let mut rotor = super::Rotors(rotor_id);
+            assert!(rotor.get_notches() == notches);
+
+            for letter in super::ALPHA {
+                assert!(rotor.is_at_notch() == notches.contains(&letter));
+                rotor.rotate(None);
+            }
+        }
+    }
+
+    #[test]
+    fn test_rotor_ring_setting() {
+        let mut rotor = super::Rotor::Rotor_1();
+        rotor.rotate_inner_ring();
+        assert!(rotor.convert('A') == 'K');
+        assert!(rotor.convert_inv('K') == 'A');
+        assert!(rotor.get_ring_setting() == 'B');
+
+        // Moving both ring and position on by one leaves the wiring core where it was
+        let mut neutral = super::Rotor::Rotor_1();
+        rotor.rotate(None);
+        for letter in super::ALPHA {
+            assert!(rotor.convert(letter) == neutral.convert(letter));
+        }
+
+        // The turnover follows the window letter regardless of the ring
+        neutral.try_set_ring_setting('H').unwrap();
+        neutral.try_set_face_letter('Q').unwrap();
+        assert!(neutral.is_at_notch());
+    }
+
+    #[test]
+    fn test_parse_ring_settings() {
+        assert!(super::try_parse_ring_settings("BUL") == Ok(vec![1, 20, 11]));
+        assert!(super::try_parse_ring_settings("bul") == Ok(vec![1, 20, 11]));
+        assert!(super::try_parse_ring_settings("02,21,12") == Ok(vec![1, 20, 11]));
+        assert!(super::try_parse_ring_settings("1 1 1 26") == Ok(vec![0, 0, 0, 25]));
+        assert!(super::try_parse_ring_settings("00,01,27") == Err(EnigmaError::InvalidRingSetting("00".to_string())));
+        assert!(super::try_parse_ring_settings("B-L") == Err(EnigmaError::InvalidRingSetting("B-L".to_string())));
+    }
+
+    #[test]
+    fn test_greek_rotors() {
+        let beta = super::Rotors(super::BETA);
+        let gamma = super::Rotors(super::GAMMA);
+        assert!(beta.is_greek() && gamma.is_greek() && !super::Rotors(1).is_greek());
+        assert!(beta.get_notches().is_empty() && gamma.get_notches().is_empty());
+
+        let beta_out: String = super::ALPHA.iter().map(|&x| beta.convert(x)).collect();
+        let gamma_out: String = super::ALPHA.iter().map(|&x| gamma.convert(x)).collect();
+        assert!(beta_out == "LEYJVCNIXWPBQMDRTAKZGFUHOS");
+        assert!(gamma_out == "FSOKANUERHMBTIYCWLQPZXVGJD");
+    }
+
     #[test]
     fn test_rotor_1_conv() {
         let rotor = super::Rotor::Rotor_1();
@@ -0,0 +1,265 @@
+use std::fmt;
+use std::str::FromStr;
+use crate::error::EnigmaError;
+use crate::rotor::try_alpha_index;
+
+// Fitness of a candidate decipherment given as alphabet indices (A = 0),
+// higher scores meaning text closer to the language expected
+pub trait Scorer {
+    fn score(&self, text: &[u8]) -> f64;
+
+    fn try_score_text(&self, text: &str) -> Result<f64, EnigmaError> {
+        let indices = text.chars()
+            .filter(|x| !x.is_whitespace())
+            .map(|x| try_alpha_index(x.to_ascii_uppercase()).map(|i| i as u8))
+            .collect::<Result<Vec<u8>, EnigmaError>>()?;
+
+        Ok(self.score(&indices))
+    }
+}
+
+pub fn index_of_coincidence(counts: &[u32; 26]) -> f64 {
+    let n_letters: u32 = counts.iter().sum();
+
+    if n_letters < 2 {
+        return 0.0;
+    }
+
+    let coincidences: u64 = counts.iter().map(|&x| x as u64 * (x as u64).saturating_sub(1)).sum();
+    coincidences as f64 / (n_letters as u64 * (n_letters as u64 - 1)) as f64
+}
+
+// Chance that two letters drawn from the text are the same, which is
+// raised in natural language whatever the letters mean
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct IndexOfCoincidence;
+
+impl Scorer for IndexOfCoincidence {
+    fn score(&self, text: &[u8]) -> f64 {
+        let mut counts = [0u32; 26];
+        for &letter in text {
+            counts[letter as usize] += 1;
+        }
+        index_of_coincidence(&counts)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    German,
+    English
+}
+
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Language::German => write!(f, "german"),
+            Language::English => write!(f, "english")
+        }
+    }
+}
+
+impl FromStr for Language {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "german" | "de" => Ok(Language::German),
+            "english" | "en" => Ok(Language::English),
+            _ => Err(format!("Unknown language '{}', expected german or english", value))
+        }
+    }
+}
+
+// Counts shipped with the crate, taken from the PostgreSQL message
+// catalogues and their German translations under the PostgreSQL Licence.
+// data/ngrams/README.md gives the source and how they were counted. Being
+// software messages rather than prose, and nothing like military German,
+// they only roughly model the plaintext of Enigma traffic.
+fn embedded_counts(language: Language, n: usize) -> Option<&'static str> {
+    match (language, n) {
+        (Language::German, 1) => Some(include_str!("../data/ngrams/german_unigrams.txt")),
+        (Language::German, 2) => Some(include_str!("../data/ngrams/german_bigrams.txt")),
+        (Language::German, 3) => Some(include_str!("../data/ngrams/german_trigrams.txt")),
+        (Language::German, 4) => Some(include_str!("../data/ngrams/german_quadgrams.txt")),
+        (Language::English, 1) => Some(include_str!("../data/ngrams/english_unigrams.txt")),
+        (Language::English, 2) => Some(include_str!("../data/ngrams/english_bigrams.txt")),
+        (Language::English, 3) => Some(include_str!("../data/ngrams/english_trigrams.txt")),
+        (Language::English, 4) => Some(include_str!("../data/ngrams/english_quadgrams.txt")),
+        _ => None
+    }
+}
+
+// Sum of the log probabilities of the n-grams of a text, those never seen
+// being given a floor of a hundredth of a single occurrence
+#[derive(Debug, Clone, PartialEq)]
+pub struct NgramScorer {
+    n: usize,
+    log_probs: Vec<f64>
+}
+
+impl NgramScorer {
+    // Reads count files of one n-gram and its count per line, e.g.
+    //
+    //     # German quadgrams
+    //     ICHT 9651
+    //     NICH 8548
+    //
+    // all n-grams in a file being the same length, from one to four letters
+    pub fn try_from_counts(counts: &str) -> Result<Self, EnigmaError> {
+        let invalid = |m: String| EnigmaError::InvalidNgramCounts(m);
+        let mut entries: Vec<(usize, u64)> = Vec::new();
+        let mut n = 0;
+
+        for line in counts.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+
+            if line.is_empty() {
+                continue;
+            }
+
+            let (gram, count) = match line.split_once(char::is_whitespace) {
+                Some((g, c)) => (g.to_ascii_uppercase(), c.trim()),
+                None => return Err(invalid(format!("expected an n-gram and its count but found '{}'", line)))
+            };
+
+            if n == 0 {
+                n = gram.len();
+                if !(1..=4).contains(&n) {
+                    return Err(invalid(format!("n-gram '{}' is not of one to four letters", gram)));
+                }
+            }
+            if gram.len() != n {
+                return Err(invalid(format!("n-gram '{}' is not of {} letters", gram, n)));
+            }
+
+            let index = gram.chars().try_fold(0, |acc, x| try_alpha_index(x).map(|i| acc * 26 + i))?;
+            let count = count.parse::<u64>().map_err(|_| invalid(format!("invalid count '{}'", count)))?;
+            entries.push((index, count));
+        }
+
+        let total: u64 = entries.iter().map(|x| x.1).sum();
+        if total == 0 {
+            return Err(invalid("no n-grams counted".to_string()));
+        }
+
+        let floor = (0.01 / total as f64).log10();
+        let mut log_probs = vec![floor; 26usize.pow(n as u32)];
+
+        for (index, count) in entries {
+            if count > 0 {
+                log_probs[index] = (count as f64 / total as f64).log10();
+            }
+        }
+
+        Ok(Self { n, log_probs })
+    }
+
+    // The shipped counts, which tell language from random letters but are
+    // best replaced through try_from_counts by those of a corpus like the
+    // traffic being attacked
+    pub fn try_embedded(language: Language, n: usize) -> Result<Self, EnigmaError> {
+        match embedded_counts(language, n) {
+            Some(counts) => Self::try_from_counts(counts),
+            None => Err(EnigmaError::InvalidNgramCounts(format!("no {}-gram counts for {}", n, language)))
+        }
+    }
+
+    pub fn embedded(language: Language, n: usize) -> Self {
+        match Self::try_embedded(language, n) {
+            Ok(s) => s,
+            Err(e) => panic!("{}", e)
+        }
+    }
+
+    pub fn get_n(&self) -> usize {
+        self.n
+    }
+}
+
+impl Scorer for NgramScorer {
+    fn score(&self, text: &[u8]) -> f64 {
+        let modulus = 26usize.pow(self.n as u32 - 1);
+        let mut index = 0;
+        let mut total = 0.0;
+
+        for (i, &letter) in text.iter().enumerate() {
+            index = (index % modulus) * 26 + letter as usize;
+            if i + 1 >= self.n {
+                total += self.log_probs[index];
+            }
+        }
+
+        total
+    }
+}
+
+// Scorer by name: "ioc", or "unigram" to "quadgram" for the given language
+pub fn try_scorer(name: &str, language: Language) -> Result<Box<dyn Scorer>, EnigmaError> {
+    let n = match name.to_ascii_lowercase().as_str() {
+        "ioc" => return Ok(Box::new(IndexOfCoincidence)),
+        "unigram" | "monogram" => 1,
+        "bigram" => 2,
+        "trigram" => 3,
+        "quadgram" | "tetragram" => 4,
+        _ => return Err(EnigmaError::InvalidNgramCounts(format!("unknown scorer '{}'", name)))
+    };
+
+    Ok(Box::new(NgramScorer::try_embedded(language, n)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{IndexOfCoincidence, Language, NgramScorer, Scorer};
+
+    #[test]
+    fn test_index_of_coincidence() {
+        let mut counts = [0u32; 26];
+        assert!(super::index_of_coincidence(&counts) == 0.0);
+
+        counts[0] = 10;
+        assert!(super::index_of_coincidence(&counts) == 1.0);
+
+        let counts = [1u32; 26];
+        assert!(super::index_of_coincidence(&counts) == 0.0);
+        assert!(IndexOfCoincidence.try_score_text("AA BB") == Ok(1.0 / 3.0));
+    }
+
+    #[test]
+    fn test_ngram_counts() {
+        let scorer = NgramScorer::try_from_counts("# test\nAB 3\nba 1\n\n").unwrap();
+        assert!(scorer.get_n() == 2);
+        assert!(scorer.score(&[0, 1]) == (0.75f64).log10());
+        assert!(scorer.score(&[0, 1, 0]) == (0.75f64).log10() + (0.25f64).log10());
+        assert!(scorer.score(&[2, 2]) == (0.01f64 / 4.0).log10());
+        assert!(scorer.score(&[0]) == 0.0);
+
+        assert!(NgramScorer::try_from_counts("AB 3\nABC 1").is_err());
+        assert!(NgramScorer::try_from_counts("AB three").is_err());
+        assert!(NgramScorer::try_from_counts("A1 3").is_err());
+        assert!(NgramScorer::try_from_counts("ABCDE 3").is_err());
+        assert!(NgramScorer::try_from_counts("# empty").is_err());
+    }
+
+    #[test]
+    fn test_embedded_scorers() {
+        let german = "DASOBERKOMMANDODERWEHRMACHTGIBTBEKANNT";
+        let english = "THEHIGHCOMMANDOFTHEARMEDFORCESANNOUNCES";
+        let garbled = "QXJZVKWPQMZXJVKQPWZXMQJVKZPXWQMJZVXKPQW";
+
+        for n in 1..=4 {
+            let de = NgramScorer::embedded(Language::German, n);
+            let en = NgramScorer::embedded(Language::English, n);
+
+            assert!(de.try_score_text(german).unwrap() > de.try_score_text(english).unwrap());
+            assert!(en.try_score_text(english).unwrap() > en.try_score_text(german).unwrap());
+            assert!(de.try_score_text(german).unwrap() > de.try_score_text(garbled).unwrap());
+        }
+
+        assert!(NgramScorer::try_embedded(Language::German, 5).is_err());
+        assert!(super::try_scorer("trigram", Language::English).is_ok());
+        assert!(super::try_scorer("pentagram", Language::English).is_err());
+        assert!("DE".parse::<Language>() == Ok(Language::German));
+        assert!("french".parse::<Language>().is_err());
+    }
+}
@@ -0,0 +1,190 @@
+use crate::bombe::Menu;
+use crate::enigma::Enigma;
+use crate::error::EnigmaError;
+use crate::rotor::{alpha_index, ALPHA};
+
+const ANY_LETTER: u32 = (1 << 26) - 1;
+
+// Plugboard connections deduced from a crib at a known rotor setting, a
+// letter paired with itself being one left without a cable. Letters of the
+// menu whose partner could not be settled are given with the partners
+// still possible, along with the connections that were tried and led to a
+// contradiction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlugboardSolution {
+    pub determined: Vec<(char, char)>,
+    pub ambiguous: Vec<(char, Vec<char>)>,
+    pub contradictions: Vec<(char, char)>
+}
+
+// Possible plugboard partners of each letter, as bit masks
+type Partners = [u32; 26];
+
+// Link of the menu as the plain and cipher letters and the scrambler
+// between them at the link's position
+type Link = (usize, usize, [u8; 26]);
+
+fn image(table: &[u8; 26], mask: u32) -> u32 {
+    (0..26).filter(|&x| mask & (1 << x) != 0).fold(0, |acc, x| acc | 1 << table[x])
+}
+
+// Narrows the partners until nothing more follows from the links, or gives
+// the letter left without any. The partner of a cipher letter is that of
+// its plain letter sent through the scrambler, and as the scrambler and
+// plugboard are both reciprocal the same holds the other way round.
+fn propagate(partners: &mut Partners, links: &[Link]) -> Result<(), char> {
+    loop {
+        let before = *partners;
+
+        for (plain, cipher, table) in links {
+            partners[*cipher] &= image(table, partners[*plain]);
+            partners[*plain] &= image(table, partners[*cipher]);
+        }
+
+        for x in 0..26 {
+            // A cable joins its letters both ways
+            let mut mask = partners[x];
+            for (y, other) in partners.iter().enumerate() {
+                if other & (1 << x) == 0 {
+                    mask &= !(1 << y);
+                }
+            }
+            partners[x] = mask;
+
+            // and a letter whose partner is settled takes it from the others
+            if mask.count_ones() == 1 {
+                let y = mask.trailing_zeros() as usize;
+                for (_, other) in partners.iter_mut().enumerate().filter(|z| z.0 != x) {
+                    *other &= !(1 << y);
+                }
+                if y != x {
+                    partners[y] &= 1 << x;
+                }
+            }
+        }
+
+        if let Some(x) = partners.iter().position(|&x| x == 0) {
+            return Err(ALPHA[x]);
+        }
+
+        if *partners == before {
+            return Ok(());
+        }
+    }
+}
+
+// Recovers the plugboard from a crib placed at the given offset, with the
+// machine's rotors set for the start of the ciphertext. Its plugboard is
+// not used. Once the links of the menu have been followed through, each
+// partner still open to a letter of the menu is tried in turn and struck
+// out if it leads to a contradiction, as the bombe's checking machine did.
+pub fn try_solve(machine: &Enigma, ciphertext: &str, crib: &str, offset: usize) -> Result<PlugboardSolution, EnigmaError> {
+    let menu = Menu::try_build(ciphertext, crib, offset)?;
+    let contradiction = |letter| EnigmaError::PlugboardContradiction { letter };
+
+    let mut scrambler = machine.clone();
+    for _ in 0..offset {
+        scrambler.step();
+    }
+
+    let links: Vec<Link> = menu.links.iter()
+        .map(|link| {
+            scrambler.step();
+            (alpha_index(link.plain), alpha_index(link.cipher), scrambler.scrambler_table())
+        })
+        .collect();
+
+    let mut partners = [ANY_LETTER; 26];
+    propagate(&mut partners, &links).map_err(contradiction)?;
+
+    let letters: Vec<usize> = menu.letters().iter().map(|&x| alpha_index(x)).collect();
+    let mut contradictions = Vec::new();
+    let mut changed = true;
+
+    while changed {
+        changed = false;
+
+        for &x in &letters {
+            let open: Vec<usize> = (0..26).filter(|&y| partners[x] & (1 << y) != 0).collect();
+
+            for y in open {
+                if partners[x].count_ones() < 2 || partners[x] & (1 << y) == 0 {
+                    continue;
+                }
+
+                let mut trial = partners;
+                trial[x] = 1 << y;
+                trial[y] &= 1 << x;
+
+                if propagate(&mut trial, &links).is_err() {
+                    partners[x] &= !(1 << y);
+                    partners[y] &= !(1 << x);
+                    contradictions.push((ALPHA[x.min(y)], ALPHA[x.max(y)]));
+
+                    propagate(&mut partners, &links).map_err(contradiction)?;
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    let determined = (0..26)
+        .filter(|&x| partners[x].count_ones() == 1 && partners[x].trailing_zeros() as usize >= x)
+        .map(|x| (ALPHA[x], ALPHA[partners[x].trailing_zeros() as usize]))
+        .collect();
+
+    let ambiguous = letters.iter()
+        .filter(|&&x| partners[x].count_ones() > 1)
+        .map(|&x| (ALPHA[x], (0..26).filter(|&y| partners[x] & (1 << y) != 0).map(|y| ALPHA[y]).collect()))
+        .collect();
+
+    contradictions.sort();
+    Ok(PlugboardSolution { determined, ambiguous, contradictions })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::enigma::Enigma;
+    use crate::error::EnigmaError;
+    use crate::plugboard::Plugboard;
+    use crate::rotor::GAMMA;
+
+    // An M4 with its Greek wheel and thin reflector, which the bombe tests
+    // leave alone, and a crib running past the turnover of the right rotor
+    const PLUGBOARD: &str = "AQ BJ CW DN EK FZ GP HS IX LV";
+    const PLAINTEXT: &str = "KEINEBESONDERENEREIGNISSEXFEINDLICHERZERSTOERERINQUADRATXANTONXSECHS";
+    const CRIB: &str = "KEINEBESONDERENEREIGNISSE";
+
+    #[test]
+    fn test_solve() {
+        let mut machine = Enigma::new(vec![GAMMA, 3, 6, 8], 'C', "M4".to_string());
+        machine.set_ring_settings("AZCM");
+        machine.set_key("TRUK".to_string());
+
+        let mut sender = machine.clone();
+        sender.set_plugboard(PLUGBOARD);
+        let cipher = sender.type_phrase(PLAINTEXT.to_string());
+        let plugboard = Plugboard::from_pairs(PLUGBOARD);
+
+        let solution = super::try_solve(&machine, &cipher, CRIB, 0).unwrap();
+        assert!(solution.determined.len() > 10 && solution.ambiguous.is_empty());
+        assert!(solution.determined.iter().all(|&(a, b)| plugboard.convert(a) == b));
+        assert!(solution.contradictions.iter().all(|&(a, b)| plugboard.convert(a) != b));
+
+        // A short crib leaves some letters open
+        let solution = super::try_solve(&machine, &cipher, &CRIB[..6], 0).unwrap();
+        assert!(!solution.ambiguous.is_empty());
+        assert!(solution.ambiguous.iter().all(|(a, partners)| partners.contains(&plugboard.convert(*a))));
+        assert!(solution.determined.iter().all(|&(a, b)| plugboard.convert(a) == b));
+
+        // The rest of the message as a crib finds every cable
+        let solution = super::try_solve(&machine, &cipher, &PLAINTEXT[26..], 26).unwrap();
+        assert!(solution.determined.iter().filter(|(a, b)| a != b).count() == 10);
+        assert!(solution.determined.iter().all(|&(a, b)| plugboard.convert(a) == b));
+
+        let mut wrong = machine.clone();
+        wrong.set_key("TRUL".to_string());
+        assert!(matches!(super::try_solve(&wrong, &cipher, CRIB, 0), Err(EnigmaError::PlugboardContradiction { .. })));
+        assert!(matches!(super::try_solve(&machine, &cipher, "", 0), Err(EnigmaError::InvalidCrib(_))));
+    }
+}
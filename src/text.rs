@@ -0,0 +1,342 @@
+use std::fmt;
+use std::str::FromStr;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use crate::error::EnigmaError;
+use crate::rotor::{try_alpha_index, ALPHA};
+
+// Numbers were spelled out digit by digit, ZWO being used over ZWEI on the
+// radio to avoid confusion with DREI
+const DIGITS: [&str; 10] = [
+    "NULL", "EINS", "ZWO", "DREI", "VIER",
+    "FUENF", "SECHS", "SIEBEN", "ACHT", "NEUN"
+];
+
+// How characters which cannot be typed on the Enigma keyboard are handled
+// when preparing a phrase for encipherment
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InputPolicy {
+    // Fail on the first character which is not a letter
+    #[default]
+    Reject,
+    // Silently discard anything which is not a letter
+    Drop,
+    // Copy anything which is not a letter to the output without enciphering it
+    PassThrough,
+    // Rewrite punctuation, numbers and umlauts following the wartime conventions
+    Transliterate
+}
+
+impl fmt::Display for InputPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            InputPolicy::Reject => "reject",
+            InputPolicy::Drop => "drop",
+            InputPolicy::PassThrough => "pass",
+            InputPolicy::Transliterate => "transliterate"
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for InputPolicy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "reject" => Ok(InputPolicy::Reject),
+            "drop" => Ok(InputPolicy::Drop),
+            "pass" | "passthrough" => Ok(InputPolicy::PassThrough),
+            "transliterate" | "translit" => Ok(InputPolicy::Transliterate),
+            _ => Err(format!("Unknown input policy '{}', expected reject, drop, pass or transliterate", value))
+        }
+    }
+}
+
+fn transliterate_char(letter: char) -> Option<&'static str> {
+    let replacement = match letter {
+        'Ä' => "AE",
+        'Ö' => "OE",
+        'Ü' => "UE",
+        'ß' => "SS",
+        '.' => "X",
+        ',' => "Y",
+        '?' => "UD",
+        ':' => "XX",
+        '-' | '/' => "YY",
+        '(' | ')' => "KK",
+        '0'..='9' => DIGITS[letter as usize - '0' as usize],
+        _ => return None
+    };
+    Some(replacement)
+}
+
+// Rewrites a phrase into letters only, following the conventions of the
+// Heer and Kriegsmarine operators. CH is replaced by Q, which was otherwise
+// almost unused in German, and anything without a convention is dropped.
+pub fn transliterate(phrase: &str) -> String {
+    let mut letters = String::with_capacity(phrase.len());
+
+    for letter in phrase.chars().flat_map(|x| x.to_uppercase()) {
+        if letter.is_ascii_uppercase() {
+            letters.push(letter);
+        }
+        else if let Some(replacement) = transliterate_char(letter) {
+            letters.push_str(replacement);
+        }
+    }
+
+    letters.replace("CH", "Q")
+}
+
+// Brings a phrase into the form typed on the machine: uppercase, without
+// whitespace, and with other characters treated according to the policy.
+// Under PassThrough the remaining characters are kept for the caller to copy.
+pub fn try_prepare(phrase: &str, policy: InputPolicy) -> Result<String, EnigmaError> {
+    let upper: String = phrase.chars()
+        .filter(|x| !x.is_whitespace())
+        .map(|x| x.to_ascii_uppercase())
+        .collect();
+
+    match policy {
+        InputPolicy::Reject => match upper.chars().find(|x| !x.is_ascii_uppercase()) {
+            Some(c) => Err(EnigmaError::InvalidLetter(c)),
+            None => Ok(upper)
+        },
+        InputPolicy::Drop => Ok(upper.chars().filter(|x| x.is_ascii_uppercase()).collect()),
+        InputPolicy::PassThrough => Ok(upper),
+        InputPolicy::Transliterate => Ok(transliterate(phrase))
+    }
+}
+
+// Undoes the most recognisable of the transliteration conventions on a
+// deciphered message so it can be read more easily, e.g. "AQTUNGX" becomes
+// "ACHTUNG." with the five letter grouping removed.
+pub fn prettify(message: &str) -> String {
+    let letters: String = message.chars().filter(|x| !x.is_whitespace()).collect();
+    let mut pretty = String::with_capacity(letters.len());
+
+    for sentence in letters.replace('Q', "CH").split('X') {
+        if sentence.is_empty() {
+            continue;
+        }
+        if !pretty.is_empty() {
+            pretty.push(' ');
+        }
+        pretty.push_str(sentence);
+        pretty.push('.');
+    }
+
+    if !letters.ends_with('X') && pretty.ends_with('.') {
+        pretty.pop();
+    }
+
+    pretty
+}
+
+// How the last group of a message is filled out to its full length
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Padding {
+    // Leave the last group short
+    None,
+    // Complete the group with a fixed letter
+    Filler(char),
+    // Complete the group with letters drawn from a random generator, seeded
+    // afresh for each message from the given seed and the message itself
+    Random(u64)
+}
+
+impl Default for Padding {
+    fn default() -> Self {
+        Padding::Filler('X')
+    }
+}
+
+impl fmt::Display for Padding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Padding::None => write!(f, "none"),
+            Padding::Filler(c) => write!(f, "{}", c),
+            Padding::Random(seed) => write!(f, "random:{}", seed)
+        }
+    }
+}
+
+impl FromStr for Padding {
+    type Err = String;
+
+    // Accepts "none", a single filler letter, "random" or "random:<seed>"
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let lower = value.to_ascii_lowercase();
+        let letters: Vec<char> = value.to_ascii_uppercase().chars().collect();
+
+        if lower == "none" {
+            Ok(Padding::None)
+        }
+        else if lower == "random" {
+            Ok(Padding::Random(rand::thread_rng().gen()))
+        }
+        else if let Some(seed) = lower.strip_prefix("random:") {
+            seed.parse().map(Padding::Random).map_err(|_| format!("Invalid padding seed '{}'", seed))
+        }
+        else if letters.len() == 1 && letters[0].is_ascii_uppercase() {
+            Ok(Padding::Filler(letters[0]))
+        }
+        else {
+            Err(format!("Invalid padding '{}', expected none, a letter, random or random:<seed>", value))
+        }
+    }
+}
+
+// Layout of enciphered text: letters are written out in groups of the given
+// size, five by default, with the last group padded out. A group size of
+// zero writes the letters as one unbroken string without any padding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputFormat {
+    pub group_size: usize,
+    pub padding: Padding
+}
+
+impl Default for OutputFormat {
+    fn default() -> Self {
+        Self {
+            group_size: 5,
+            padding: Padding::default()
+        }
+    }
+}
+
+impl OutputFormat {
+    pub fn ungrouped() -> Self {
+        Self {
+            group_size: 0,
+            padding: Padding::None
+        }
+    }
+
+    // Letters to append to a message sent at the given key to complete its
+    // last group
+    pub fn try_padding(&self, message: &str, key: &str) -> Result<String, EnigmaError> {
+        let n_letters = message.chars().filter(|x| x.is_ascii_uppercase()).count();
+
+        if self.group_size == 0 || n_letters.is_multiple_of(self.group_size) {
+            return Ok(String::new());
+        }
+
+        let remainder = self.group_size - n_letters % self.group_size;
+
+        match self.padding {
+            Padding::None => Ok(String::new()),
+            Padding::Filler(c) => {
+                try_alpha_index(c)?;
+                Ok(std::iter::repeat_n(c, remainder).collect())
+            },
+            Padding::Random(seed) => {
+                // FNV-1a over the key and message, so that no two messages
+                // share their filler and a seed gives the same on any build
+                let seed = key.bytes().chain(message.bytes())
+                    .fold(seed ^ 0xcbf29ce484222325, |acc, x| (acc ^ x as u64).wrapping_mul(0x100000001b3));
+                let mut rng = StdRng::seed_from_u64(seed);
+                Ok((0..remainder).map(|_| ALPHA[rng.gen_range(0..26)]).collect())
+            }
+        }
+    }
+
+    // Splits text into groups counting only the letters, so any characters
+    // let through by the input policy stay attached to their neighbours
+    pub fn group(&self, message: &str) -> String {
+        let mut grouped = String::with_capacity(message.len() + message.len() / 4);
+        let mut n_letters = 0;
+
+        for letter in message.chars().filter(|x| !x.is_whitespace()) {
+            if letter.is_ascii_uppercase() {
+                if self.group_size > 0 && n_letters > 0 && n_letters % self.group_size == 0 {
+                    grouped.push(' ');
+                }
+                n_letters += 1;
+            }
+            grouped.push(letter);
+        }
+
+        grouped
+    }
+}
+
+// Removes the group spacing from received text ahead of deciphering
+pub fn strip_groups(message: &str) -> String {
+    message.chars().filter(|x| !x.is_whitespace()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::InputPolicy;
+    use crate::error::EnigmaError;
+
+    #[test]
+    fn test_prepare() {
+        let phrase = "Fuel: 25 t.";
+        assert!(super::try_prepare(phrase, InputPolicy::Reject) == Err(EnigmaError::InvalidLetter(':')));
+        assert!(super::try_prepare(phrase, InputPolicy::Drop) == Ok("FUELT".to_string()));
+        assert!(super::try_prepare(phrase, InputPolicy::PassThrough) == Ok("FUEL:25T.".to_string()));
+        assert!(super::try_prepare(phrase, InputPolicy::Transliterate) == Ok("FUELXXZWOFUENFTX".to_string()));
+    }
+
+    #[test]
+    fn test_transliterate() {
+        assert!(super::transliterate("Nacht über München.") == "NAQTUEBERMUENQENX");
+        assert!(super::transliterate("Straße 10?") == "STRASSEEINSNULLUD");
+    }
+
+    #[test]
+    fn test_prettify() {
+        assert!(super::prettify("AQTUN GXFEI NDSIQ TX") == "ACHTUNG. FEINDSICHT.");
+        assert!(super::prettify("UBOOT XSTOP") == "UBOOT. STOP");
+        assert!(super::prettify(&super::transliterate("Acht Schiffe.")) == "ACHTSCHIFFE.");
+    }
+
+    #[test]
+    fn test_output_format() {
+        use super::{OutputFormat, Padding};
+
+        let format = OutputFormat::default();
+        assert!(format.try_padding("ABCDEFGHIJKL", "AAA") == Ok("XXX".to_string()));
+        assert!(format.try_padding("ABCDE, FGHIJKLMNO", "AAA") == Ok(String::new()));
+        assert!(format.group("ABCDEFGHIJKL") == "ABCDE FGHIJ KL");
+        assert!(format.group("ABC, DEFGH!") == "ABC,DE FGH!");
+        assert!(super::strip_groups("ABCDE FGHIJ\nKL") == "ABCDEFGHIJKL");
+
+        let format = OutputFormat { group_size: 4, padding: Padding::Random(42) };
+        let padding = format.try_padding("ABCDE", "AAA").unwrap();
+        assert!(padding.len() == 3 && padding == format.try_padding("ABCDE", "AAA").unwrap());
+
+        // Each message, or the same one sent at another key, is padded differently
+        assert!(padding != format.try_padding("ABCDF", "AAA").unwrap());
+        assert!(padding != format.try_padding("ABCDE", "AAB").unwrap());
+        assert!(format.try_padding("ABCDEFGHI", "AAA").unwrap().len() == 3);
+
+        let format = OutputFormat { group_size: 4, padding: Padding::Filler('!') };
+        assert!(format.try_padding("ABCDE", "AAA") == Err(EnigmaError::InvalidLetter('!')));
+        assert!(OutputFormat::ungrouped().group("ABCDE FGHIJ") == "ABCDEFGHIJ");
+        assert!(OutputFormat::ungrouped().try_padding("ABCDEFG", "AAA") == Ok(String::new()));
+    }
+
+    #[test]
+    fn test_padding_names() {
+        use super::Padding;
+
+        assert!("none".parse::<Padding>() == Ok(Padding::None));
+        assert!("q".parse::<Padding>() == Ok(Padding::Filler('Q')));
+        assert!("random:7".parse::<Padding>() == Ok(Padding::Random(7)));
+        assert!(Padding::Random(7).to_string().parse::<Padding>() == Ok(Padding::Random(7)));
+        assert!("random:x".parse::<Padding>().is_err());
+        assert!("XY".parse::<Padding>().is_err());
+    }
+
+    #[test]
+    fn test_policy_names() {
+        for policy in [InputPolicy::Reject, InputPolicy::Drop, InputPolicy::PassThrough, InputPolicy::Transliterate] {
+            assert!(policy.to_string().parse::<InputPolicy>() == Ok(policy));
+        }
+        assert!("shout".parse::<InputPolicy>().is_err());
+    }
+}
@@ -0,0 +1,249 @@
+use crate::bombe::Scrambler;
+use crate::error::EnigmaError;
+use crate::indicator::{Indicator, Procedure};
+use crate::rotor::{self, try_alpha_index, ALPHA};
+
+// Size in pixels of one cell of a sheet drawn as SVG
+const SVG_CELL: usize = 16;
+
+// Perforated sheet for one wheel order and position of the left rotor. A
+// hole is punched at (middle, right) where a message key doubled from that
+// start position can give a female, the same letter enciphered in first and
+// fourth place, which needs AD to have a fixed point. As in the original
+// sheets, the middle and left rotors are taken not to turn during the six
+// letters of the indicator.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sheet {
+    pub rotors: Vec<String>,
+    pub left: char,
+    pub holes: [[bool; 26]; 26]
+}
+
+// Wheel order and ring settings found by stacking the sheets, with the
+// number of females whose sheets had a hole in the place found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SheetSolution {
+    pub rotors: Vec<String>,
+    pub rings: String,
+    pub matched: usize,
+    pub females: usize
+}
+
+fn has_female(scrambler: &mut Scrambler) -> bool {
+    let mut tables = [[0u8; 26]; 4];
+    for table in tables.iter_mut() {
+        scrambler.step_right();
+        *table = scrambler.table();
+    }
+
+    (0..26).any(|x| tables[3][tables[0][x] as usize] as usize == x)
+}
+
+impl Sheet {
+    pub fn is_hole(&self, middle: char, right: char) -> bool {
+        self.holes[rotor::alpha_index(middle)][rotor::alpha_index(right)]
+    }
+
+    pub fn n_holes(&self) -> usize {
+        self.holes.iter().flatten().filter(|&&x| x).count()
+    }
+
+    // Rows for the middle rotor and columns for the right, holes shown as 'O'
+    pub fn to_ascii(&self) -> String {
+        let mut text = format!("{} {}\n  {}\n", self.rotors.join(" "), self.left, ALPHA.iter().collect::<String>());
+
+        for (middle, row) in self.holes.iter().enumerate() {
+            text.push(ALPHA[middle]);
+            text.push(' ');
+            text.extend(row.iter().map(|&x| if x {'O'} else {'.'}));
+            text.push('\n');
+        }
+
+        text
+    }
+
+    pub fn to_svg(&self) -> String {
+        let size = SVG_CELL * 27;
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{0}\" viewBox=\"0 0 {0} {0}\">\n\
+             <title>{1} {2}</title>\n<rect width=\"{0}\" height=\"{0}\" fill=\"#d8c9a3\"/>\n",
+            size, self.rotors.join(" "), self.left);
+
+        for (i, letter) in ALPHA.iter().enumerate() {
+            let centre = SVG_CELL * (i + 1) + SVG_CELL / 2;
+            svg += &format!(
+                "<text x=\"{0}\" y=\"{1}\" font-size=\"{2}\" text-anchor=\"middle\">{3}</text>\n\
+                 <text x=\"{1}\" y=\"{4}\" font-size=\"{2}\" text-anchor=\"middle\">{3}</text>\n",
+                centre, SVG_CELL * 3 / 4, SVG_CELL * 3 / 4, letter, centre + SVG_CELL / 4);
+        }
+
+        for (middle, row) in self.holes.iter().enumerate() {
+            for (right, _) in row.iter().enumerate().filter(|x| *x.1) {
+                svg += &format!(
+                    "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"white\"/>\n",
+                    SVG_CELL * (right + 1) + SVG_CELL / 2, SVG_CELL * (middle + 1) + SVG_CELL / 2, SVG_CELL * 3 / 8);
+            }
+        }
+
+        svg + "</svg>\n"
+    }
+}
+
+// The 26 sheets of one wheel order, one for each position of the left rotor.
+// The sheets predate the M4, so only three rotor orders are accepted.
+pub fn try_generate(rotors: &[i32], reflector: char) -> Result<Vec<Sheet>, EnigmaError> {
+    if rotors.len() != 3 {
+        return Err(EnigmaError::WrongRotorCount { model: "M3".to_string(), expected: 3, found: rotors.len() });
+    }
+
+    let mut scrambler = Scrambler::try_new(rotors, reflector)?;
+    let names: Vec<String> = rotors.iter()
+        .map(|x| rotor::try_rotors(*x).map(|r| r.get_name()))
+        .collect::<Result<Vec<String>, EnigmaError>>()?;
+
+    let mut sheets = Vec::with_capacity(26);
+
+    for (left, &letter) in ALPHA.iter().enumerate() {
+        let mut holes = [[false; 26]; 26];
+
+        for (middle, row) in holes.iter_mut().enumerate() {
+            for (right, hole) in row.iter_mut().enumerate() {
+                scrambler.set_start((left * 26 + middle) * 26 + right);
+                *hole = has_female(&mut scrambler);
+            }
+        }
+
+        sheets.push(Sheet { rotors: names.clone(), left: letter, holes });
+    }
+
+    Ok(sheets)
+}
+
+// Window positions at which the intercepted indicators show a female, the
+// right rotor moved on to the first letter of the pair
+fn females(indicators: &[Indicator]) -> Result<Vec<[usize; 3]>, EnigmaError> {
+    let mut found = Vec::new();
+
+    for indicator in indicators {
+        if indicator.procedure != Procedure::Doubled {
+            return Err(EnigmaError::MalformedIndicator(format!("indicator '{}' is not doubled", indicator)));
+        }
+
+        let window = indicator.grundstellung.chars()
+            .map(try_alpha_index)
+            .collect::<Result<Vec<usize>, EnigmaError>>()?;
+        let key: Vec<char> = indicator.enciphered_key.chars().collect();
+
+        if window.len() != 3 || key.len() != 6 {
+            return Err(EnigmaError::MalformedIndicator(format!(
+                "expected a three letter Grundstellung with six letters but found '{} {}'",
+                indicator.grundstellung, indicator.enciphered_key)));
+        }
+
+        for k in (0..3).filter(|&k| key[k] == key[k + 3]) {
+            found.push([window[0], window[1], (window[2] + k) % 26]);
+        }
+    }
+
+    Ok(found)
+}
+
+// Stacks the sheets for the females among indicators sent under the
+// 1938 procedure, each with its own Grundstellung in clear, and looks for
+// places where light shines through all of them. Each left ring setting
+// chooses the sheets to stack and each middle and right ring setting the
+// shift of the sheets against one another. Up to `tolerance` females may
+// be missed, to allow for the turnovers the sheets leave out. Solutions are
+// ranked by the number of sheets with a hole in the place.
+pub fn try_solve(indicators: &[Indicator], orders: &[Vec<i32>], reflector: char, tolerance: usize) -> Result<Vec<SheetSolution>, EnigmaError> {
+    let females = females(indicators)?;
+    let needed = females.len().saturating_sub(tolerance).max(1);
+    let mut solutions = Vec::new();
+
+    if females.is_empty() {
+        return Ok(solutions);
+    }
+
+    for order in orders {
+        let sheets = try_generate(order, reflector)?;
+
+        for left_ring in 0..26 {
+            for middle_ring in 0..26 {
+                for right_ring in 0..26 {
+                    let matched = females.iter()
+                        .filter(|f| sheets[(f[0] + 26 - left_ring) % 26].holes[(f[1] + 26 - middle_ring) % 26][(f[2] + 26 - right_ring) % 26])
+                        .count();
+
+                    if matched >= needed {
+                        solutions.push(SheetSolution {
+                            rotors: sheets[0].rotors.clone(),
+                            rings: [left_ring, middle_ring, right_ring].iter().map(|&x| ALPHA[x]).collect(),
+                            matched,
+                            females: females.len()
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    solutions.sort_by_key(|x| std::cmp::Reverse(x.matched));
+    Ok(solutions)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use crate::enigma::Enigma;
+    use crate::indicator::{self, Indicator, Procedure};
+
+    #[test]
+    fn test_sheets() {
+        let sheets = super::try_generate(&[1, 2, 3], 'B').unwrap();
+        assert!(sheets.len() == 26 && sheets[3].left == 'D');
+
+        // Roughly two in five positions give females
+        let holes: usize = sheets.iter().map(|x| x.n_holes()).sum();
+        assert!(holes > 26 * 676 / 4 && holes < 26 * 676 / 2);
+
+        let ascii = sheets[0].to_ascii();
+        assert!(ascii.lines().count() == 28 && ascii.starts_with("I II III A\n"));
+        assert!(ascii.lines().nth(2).unwrap().starts_with("A "));
+        assert!(ascii.lines().skip(2).map(|x| x[2..].matches('O').count()).sum::<usize>() == sheets[0].n_holes());
+
+        let svg = sheets[0].to_svg();
+        assert!(svg.starts_with("<svg") && svg.trim_end().ends_with("</svg>"));
+        assert!(svg.matches("<circle").count() == sheets[0].n_holes());
+        assert!(super::try_generate(&[1, 1, 3], 'B').is_err());
+        assert!(super::try_generate(&[9, 1, 2, 3], 'B').is_err());
+    }
+
+    #[test]
+    fn test_solve() {
+        let mut machine = Enigma::new(vec![2, 1, 3], 'B', "M3".to_string());
+        machine.set_ring_settings("KUX");
+        machine.set_plugboard("AQ BW CE DR FT GZ");
+
+        // A day's traffic under the 1938 procedure
+        let mut rng = StdRng::seed_from_u64(1938);
+        let indicators: Vec<Indicator> = (0..120)
+            .map(|_| {
+                let grundstellung = indicator::random_key(&mut rng, 3);
+                let key = indicator::random_key(&mut rng, 3);
+                Indicator::try_create(&machine, Procedure::Doubled, &grundstellung, &key).unwrap()
+            })
+            .collect();
+
+        let n_females = super::females(&indicators).unwrap().len();
+        assert!(n_females > 10);
+
+        let solutions = super::try_solve(&indicators, &[vec![1, 2, 3], vec![2, 1, 3]], 'B', 1).unwrap();
+        assert!(solutions[0].rotors == vec!["II", "I", "III"] && solutions[0].rings == "KUX");
+        assert!(solutions[0].matched == n_females && solutions[1].matched < n_females);
+        assert!(solutions.len() < 10);
+
+        let single = Indicator::try_create(&machine, Procedure::Single, "ABC", "DEF").unwrap();
+        assert!(super::try_solve(&[single], &[vec![1, 2, 3]], 'B', 0).is_err());
+    }
+}
@@ -0,0 +1,149 @@
+// Published plaintext/ciphertext pairs with full machine settings, checked
+// against the public API in both directions.
+
+use enigma::Enigma;
+use enigma::rotor::BETA;
+
+struct KnownAnswer {
+    name: &'static str,
+    model: &'static str,
+    rotors: Vec<i32>,
+    reflector: char,
+    rings: &'static str,
+    plugboard: &'static str,
+    start: &'static str,
+    plaintext: &'static str,
+    ciphertext: &'static str
+}
+
+impl KnownAnswer {
+    fn machine(&self) -> Enigma {
+        let mut machine = Enigma::new(self.rotors.clone(), self.reflector, self.model.to_string());
+        machine.set_ring_settings(self.rings);
+        machine.set_plugboard(self.plugboard);
+        machine.set_key(self.start.to_string());
+        machine
+    }
+}
+
+fn type_text(machine: &mut Enigma, text: &str) -> String {
+    text.chars().filter(|x| !x.is_whitespace()).map(|x| machine.type_letter(x)).collect()
+}
+
+fn strip(text: &str) -> String {
+    text.chars().filter(|x| !x.is_whitespace()).collect()
+}
+
+fn corpus() -> Vec<KnownAnswer> {
+    vec![
+        KnownAnswer {
+            name: "Enigma I, wheels I II III at AAA",
+            model: "M3",
+            rotors: vec![1, 2, 3],
+            reflector: 'B',
+            rings: "AAA",
+            plugboard: "",
+            start: "AAA",
+            plaintext: "AAAAA AAAAA",
+            ciphertext: "BDZGO WCXLT"
+        },
+        KnownAnswer {
+            name: "Enigma I operating manual, 1930",
+            model: "M3",
+            rotors: vec![2, 1, 3],
+            reflector: 'A',
+            rings: "24 13 22",
+            plugboard: "AM FI NV PS TU WZ",
+            start: "ABL",
+            plaintext: "FEIND LIQEI NFANT ERIEK OLONN EBEOB AQTET XANFA NGSUE DAUSG \
+                        ANGBA ERWAL DEXEN DEDRE IKMOS TWAER TSNEU STADT",
+            ciphertext: "GCDSE AHUGW TQGRK VLFGX UCALX VYMIG MMNMF DXTGN VHVRM MEVOU \
+                         YFZSL RHDRR XFJWC FHUHM UNZEF RDISI KBGPM YVXUZ"
+        },
+        KnownAnswer {
+            name: "Operation Barbarossa, 7th July 1941, part 1",
+            model: "M3",
+            rotors: vec![2, 4, 5],
+            reflector: 'B',
+            rings: "02 21 12",
+            plugboard: "AV BS CG DL FU HZ IN KM OW RX",
+            start: "BLA",
+            plaintext: "AUFKL XABTE ILUNG XVONX KURTI NOWAX KURTI NOWAX NORDW ESTLX \
+                        SEBEZ XSEBE ZXUAF FLIEG ERSTR ASZER IQTUN GXDUB ROWKI XDUBR \
+                        OWKIX OPOTS CHKAX OPOTS CHKAX UMXEI NSAQT DREIN ULLXU HRANG \
+                        ETRET ENXAN GRIFF XINFX RGTX",
+            ciphertext: "EDPUD NRGYS ZRCXN UYTPO MRMBO FKTBZ REZKM LXLVE FGUEY SIOZV \
+                         EQMIK UBPMM YLKLT TDEIS MDICA GYKUA CTCDO MOHWX MUUIA UBSTS \
+                         LRNBZ SZWNR FXWFY SSXJZ VIJHI DISHP RKLKA YUPAD TXQSP INQMA \
+                         TLPIF SVKDA SCTAC DPBOP VHJK"
+        },
+        KnownAnswer {
+            name: "Operation Barbarossa, 7th July 1941, part 2",
+            model: "M3",
+            rotors: vec![2, 4, 5],
+            reflector: 'B',
+            rings: "02 21 12",
+            plugboard: "AV BS CG DL FU HZ IN KM OW RX",
+            start: "LSD",
+            plaintext: "DREIG EHTLA NGSAM ABERS IQERV ORWAE RTSXE INSSI EBENN ULLSE \
+                        QSXUH RXROE MXEIN SXINF RGTXD REIXA UFFLI EGERS TRASZ EMITA \
+                        NFANG XEINS SEQSX KMXKM XOSTW XKAME NECXK",
+            ciphertext: "SFBWD NJUSE GQOBH KRTAR EEZMW KPPRB XOHDR OEQGB BGTQV PGVKB \
+                         VVGBI MHUSZ YDAJQ IROAX SSSNR EHYGG RPISE ZBOVM QIEMM ZCYSG \
+                         QDGRE RVBIL EKXYQ IRGIR QNRDN VRXCY YTNJR"
+        },
+        KnownAnswer {
+            name: "Grand Admiral Doenitz, 1st May 1945",
+            model: "M4",
+            rotors: vec![BETA, 5, 6, 8],
+            reflector: 'C',
+            rings: "AAEL",
+            plugboard: "AE BF CM DQ HU JN LX PR SZ VW",
+            start: "YOSZ",
+            plaintext: "KRKRA LLEXX FOLGE NDESI STSOF ORTBE KANNT ZUGEB ENXXI CHHAB \
+                        EFOLG ELNBE BEFEH LERHA LTENX XJANS TERLE DESBI SHERI GXNRE \
+                        ICHSM ARSCH ALLSJ GOERI NGJSE TZTDE RFUEH RERSI EYHVR RGRZS \
+                        SADMI RALYA LSSEI NENNA CHFOL GEREI NXSCH RIFTL SCHEV OLLMA \
+                        CHTUN TERWE GSXAB SOFOR TSOLL ENSIE SAEMT LICHE MASSN AHMEN \
+                        VERFU EGENY DIESI CHAUS DERGE GENWA ERTIG ENLAG EERGE BENXG \
+                        EZXRE ICHSL EITEI KKTUL PEKKJ BORMA NNJXX OBXDX MMMDU RNHFK \
+                        STXKO MXADM XUUUB OOIEX KP",
+            ciphertext: "LANOT CTOUA RBBFP MHPHG CZXTD YGAHG UFXGE WKBLK GJWLQ XXTGP \
+                         JJAVT OCKZF SLPPQ IHZFX OEBWI IEKFZ LCLOA QJULJ OYHSS MBBGW \
+                         HZANV OIIPY RBRTD JQDJJ OQKCX WDNBB TYVXL YTAPG VEATX SONPN \
+                         YNQFU DBBHH VWEPY EYDOH NLXKZ DNWRH DUWUJ UMWWV IIWZX IVIUQ \
+                         DRHYM NCYEF UAPNH OTKHK GDNPS AKNUA GHJZS MJBMH VTREQ EDGXH \
+                         LZWIF USKDQ VELNM IMITH BHDBW VHDFY HJOQI HORTD JDBWX EMEAY \
+                         XGYQX OHFDM YUXXN OJAZR SGHPL WMLRE CWWUT LRTTV LBHYO ORGLG \
+                         OWUXN XHMHY FAACQ EKTHS JW"
+        }
+    ]
+}
+
+#[test]
+fn test_known_answer_encryption() {
+    for vector in corpus() {
+        let mut machine = vector.machine();
+        assert_eq!(type_text(&mut machine, vector.plaintext), strip(vector.ciphertext), "{}", vector.name);
+    }
+}
+
+#[test]
+fn test_known_answer_decryption() {
+    for vector in corpus() {
+        let mut machine = vector.machine();
+        assert_eq!(type_text(&mut machine, vector.ciphertext), strip(vector.plaintext), "{}", vector.name);
+    }
+}
+
+#[test]
+fn test_barbarossa_message_keys() {
+    // Indicators sent in clear as "WXC KCH" and "CRS YPJ"
+    for (start, indicator, message_key) in [("WXC", "KCH", "BLA"), ("CRS", "YPJ", "LSD")] {
+        let mut machine = Enigma::new(vec![2, 4, 5], 'B', "M3".to_string());
+        machine.set_ring_settings("BUL");
+        machine.set_plugboard("AV BS CG DL FU HZ IN KM OW RX");
+        machine.set_key(start.to_string());
+        assert_eq!(type_text(&mut machine, indicator), message_key);
+    }
+}